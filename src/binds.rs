@@ -1,17 +1,37 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::{bail, Result};
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde::Deserialize;
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct BindMap<Action>(HashMap<KeyEvent, Binding<Action>>);
 
+// Implemented by hand as deriving would require Action: Default.
+impl<Action> Default for BindMap<Action> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
 impl<Action> BindMap<Action> {
     pub fn new<T: Into<HashMap<KeyEvent, Binding<Action>>>>(map: T) -> Self {
         Self(map.into())
     }
+
+    // Adds the binds from other, replacing existing binds for the same key. Chains present in
+    // both are merged rather than replaced.
+    pub fn overlay(&mut self, other: BindMap<Action>) {
+        for (key, binding) in other.0 {
+            match (self.0.get_mut(&key), binding) {
+                (Some(Binding::Chain(existing)), Binding::Chain(chain)) => existing.overlay(chain),
+                (_, binding) => {
+                    self.0.insert(key, binding);
+                }
+            }
+        }
+    }
 }
 
 impl<'de, Action> Deserialize<'de> for BindMap<Action>
@@ -40,6 +60,8 @@ where
 pub enum Binding<Action> {
     Action(Vec<Action>),
     Chain(BindMap<Action>),
+    // Actions run in turn, each followed by an optional wait before the next.
+    Macro(Vec<(Action, Option<Duration>)>),
 }
 
 impl<'de, Action> Deserialize<'de> for Binding<Action>
@@ -50,18 +72,36 @@ where
     where
         D: serde::Deserializer<'de>,
     {
+        // a step is an action, or an action and the milliseconds to wait after it
+        #[derive(Debug, Deserialize)]
+        #[serde(untagged)]
+        pub enum Step<Action> {
+            Action(Action),
+            Wait(Action, u64),
+        }
+
         #[derive(Debug, Deserialize)]
-        #[cfg_attr(test, derive(PartialEq))]
         #[serde(untagged)]
         pub enum Serialized<Action> {
             Single(Action),
             Multi(Vec<Action>),
+            // must come before Chain, which would reject "macro" as a key
+            Macro { r#macro: Vec<Step<Action>> },
             Chain(BindMap<Action>),
         }
         let parsed = Serialized::deserialize(deserializer)?;
         Ok(match parsed {
             Serialized::Single(a) => Binding::Action(vec![a]),
             Serialized::Multi(a) => Binding::Action(a),
+            Serialized::Macro { r#macro } => Binding::Macro(
+                r#macro
+                    .into_iter()
+                    .map(|step| match step {
+                        Step::Action(a) => (a, None),
+                        Step::Wait(a, ms) => (a, Some(Duration::from_millis(ms))),
+                    })
+                    .collect(),
+            ),
             Serialized::Chain(c) => Binding::Chain(c),
         })
     }
@@ -78,15 +118,16 @@ impl<Action> Binds<Action> {
         Self { map, keys: vec![] }
     }
 
-    pub fn apply(&mut self, key: KeyEvent) -> Option<&Vec<Action>> {
+    // Returns the binding completed by key, which is never a Chain.
+    pub fn apply(&mut self, key: KeyEvent) -> Option<&Binding<Action>> {
         let mut bound = &self.map;
-        self.keys.push(key);
+        self.keys.push(canonical(key));
         for k in &self.keys {
-            bound = match bound.0.get(&k) {
+            bound = match bound.0.get(k) {
                 Some(Binding::Chain(c)) => c,
-                Some(Binding::Action(a)) => {
+                Some(binding) => {
                     self.keys.clear();
-                    return Some(a);
+                    return Some(binding);
                 }
                 None => {
                     log::trace!("{:?} bound to nothing", self.keys);
@@ -100,6 +141,18 @@ impl<Action> Binds<Action> {
     }
 }
 
+// Returns a plain press of the key, with shift folded into the character. Terminals may
+// report shift+a as either 'A' or 'a' with SHIFT, so both must end up the same.
+fn canonical(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::SHIFT) => KeyEvent::new(
+            KeyCode::Char(c.to_ascii_uppercase()),
+            key.modifiers - KeyModifiers::SHIFT,
+        ),
+        code => KeyEvent::new(code, key.modifiers),
+    }
+}
+
 fn map_key(key: &str) -> Result<KeyEvent> {
     let mut parts = key.split('-').rev();
     let Some(code) = parts.next() else {
@@ -147,19 +200,14 @@ fn map_key(key: &str) -> Result<KeyEvent> {
             m => bail!(format!("Unknown key modifier: {m}")),
         });
     }
-    Ok(KeyEvent {
-        code,
-        modifiers,
-        kind: KeyEventKind::Press,
-        state: KeyEventState::empty(),
-    })
+    Ok(canonical(KeyEvent::new(code, modifiers)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[derive(PartialEq, Debug, Deserialize)]
+    #[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
     #[serde(rename_all = "snake_case")]
     enum Action {
         One,
@@ -191,7 +239,7 @@ mod tests {
 
         assert_eq!(
             binds.apply(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::empty())),
-            Some(&vec![One])
+            Some(&Binding::Action(vec![One]))
         );
 
         for ev in [
@@ -199,7 +247,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('s'), KeyModifiers::SHIFT),
             KeyEvent::new(KeyCode::Char('S'), KeyModifiers::SHIFT),
         ] {
-            assert_eq!(binds.apply(ev), Some(&vec![Two]));
+            assert_eq!(binds.apply(ev), Some(&Binding::Action(vec![Two])));
         }
 
         for ev in [
@@ -207,7 +255,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('l'), KeyModifiers::SHIFT),
             KeyEvent::new(KeyCode::Char('L'), KeyModifiers::SHIFT),
         ] {
-            assert_eq!(binds.apply(ev), Some(&vec![Three]));
+            assert_eq!(binds.apply(ev), Some(&Binding::Action(vec![Three])));
         }
 
         for ev in [
@@ -215,12 +263,12 @@ mod tests {
             KeyEvent::new(KeyCode::Char('x'), KeyModifiers::SHIFT),
             KeyEvent::new(KeyCode::Char('X'), KeyModifiers::SHIFT),
         ] {
-            assert_eq!(binds.apply(ev), Some(&vec![Four]));
+            assert_eq!(binds.apply(ev), Some(&Binding::Action(vec![Four])));
         }
 
         assert_eq!(
             binds.apply(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)),
-            Some(&vec![Four, Four])
+            Some(&Binding::Action(vec![Four, Four]))
         );
 
         assert_eq!(
@@ -239,7 +287,7 @@ mod tests {
         );
         assert_eq!(
             binds.apply(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::empty())),
-            Some(&vec![Four]),
+            Some(&Binding::Action(vec![Four])),
         );
 
         // space - enter
@@ -249,7 +297,170 @@ mod tests {
         );
         assert_eq!(
             binds.apply(KeyEvent::new(KeyCode::Enter, KeyModifiers::empty())),
-            Some(&vec![One, Two]),
+            Some(&Binding::Action(vec![One, Two])),
+        );
+    }
+
+    #[test]
+    fn test_binds_shifted() {
+        use Action::*;
+
+        let map: BindMap<Action> = toml::from_str(
+            &toml::toml! {
+                A = "one"
+                S-b = "two"
+                C-S-c = "three"
+            }
+            .to_string(),
+        )
+        .unwrap();
+
+        let upper = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::empty());
+        assert_eq!(map_key("A").unwrap(), upper);
+        assert_eq!(map_key("S-a").unwrap().code, KeyCode::Char('A'));
+        assert_eq!(map_key("S-a").unwrap().modifiers, KeyModifiers::empty());
+
+        let mut binds = Binds::new(map);
+        for (c, action) in [('a', One), ('b', Two)] {
+            for ev in [
+                KeyEvent::new(KeyCode::Char(c.to_ascii_uppercase()), KeyModifiers::empty()),
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::SHIFT),
+                KeyEvent::new(KeyCode::Char(c.to_ascii_uppercase()), KeyModifiers::SHIFT),
+            ] {
+                assert_eq!(
+                    binds.apply(ev),
+                    Some(&Binding::Action(vec![action])),
+                    "{ev:?}"
+                );
+            }
+        }
+
+        for ev in [
+            KeyEvent::new(KeyCode::Char('C'), KeyModifiers::CONTROL),
+            KeyEvent::new(
+                KeyCode::Char('c'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            ),
+        ] {
+            assert_eq!(
+                binds.apply(ev),
+                Some(&Binding::Action(vec![Three])),
+                "{ev:?}"
+            );
+        }
+        assert_eq!(
+            binds.apply(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::empty())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_binds_modified_chain() {
+        use Action::*;
+
+        let map: BindMap<Action> = toml::from_str(
+            &toml::toml! {
+                [g]
+                C-l = "one"
+                l = "two"
+                [C-w]
+                A-h = "three"
+            }
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut binds = Binds::new(map);
+        let plain = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+
+        assert_eq!(binds.apply(plain('g')), None);
+        assert_eq!(binds.apply(ctrl('l')), Some(&Binding::Action(vec![One])));
+        assert_eq!(binds.apply(plain('g')), None);
+        assert_eq!(binds.apply(plain('l')), Some(&Binding::Action(vec![Two])));
+
+        // modifiers must match exactly
+        assert_eq!(binds.apply(plain('g')), None);
+        assert_eq!(
+            binds.apply(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::ALT)),
+            None
+        );
+
+        // extra state reported by some terminals is ignored
+        assert_eq!(binds.apply(ctrl('w')), None);
+        let mut key = KeyEvent::new(KeyCode::Char('h'), KeyModifiers::ALT);
+        key.state = crossterm::event::KeyEventState::CAPS_LOCK;
+        assert_eq!(binds.apply(key), Some(&Binding::Action(vec![Three])));
+    }
+
+    #[test]
+    fn test_overlay() {
+        use Action::*;
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
+
+        let mut map: BindMap<Action> = toml::from_str(
+            &toml::toml! {
+                a = "one"
+                b = "two"
+                [g]
+                a = "one"
+                b = "two"
+            }
+            .to_string(),
+        )
+        .unwrap();
+        map.overlay(
+            toml::from_str(
+                &toml::toml! {
+                    b = "three"
+                    c = "four"
+                    [g]
+                    b = "four"
+                }
+                .to_string(),
+            )
+            .unwrap(),
+        );
+
+        let mut binds = Binds::new(map);
+        assert_eq!(binds.apply(key('a')), Some(&Binding::Action(vec![One])));
+        assert_eq!(binds.apply(key('b')), Some(&Binding::Action(vec![Three])));
+        assert_eq!(binds.apply(key('c')), Some(&Binding::Action(vec![Four])));
+        // chains are merged
+        binds.apply(key('g'));
+        assert_eq!(binds.apply(key('a')), Some(&Binding::Action(vec![One])));
+        binds.apply(key('g'));
+        assert_eq!(binds.apply(key('b')), Some(&Binding::Action(vec![Four])));
+    }
+
+    #[test]
+    fn test_binds_macro() {
+        use Action::*;
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
+
+        let map: BindMap<Action> = toml::from_str(
+            &toml::toml! {
+                m = { macro = [["one", 1000], "two", ["three", 50]] }
+                [g]
+                m = { macro = ["four"] }
+            }
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut binds = Binds::new(map);
+        assert_eq!(
+            binds.apply(key('m')),
+            Some(&Binding::Macro(vec![
+                (One, Some(Duration::from_secs(1))),
+                (Two, None),
+                (Three, Some(Duration::from_millis(50))),
+            ]))
+        );
+        assert_eq!(binds.apply(key('g')), None);
+        assert_eq!(
+            binds.apply(key('m')),
+            Some(&Binding::Macro(vec![(Four, None)]))
         );
     }
 }
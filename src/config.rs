@@ -1,75 +1,608 @@
-use anyhow::Result;
+use std::path::Path;
+
+use anyhow::{bail, Result};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde::Deserialize;
 
-use crate::binds::{BindMap, Binding};
+use crate::{
+    binds::{BindMap, Binding},
+    encode::WavBits,
+};
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case")]
 pub enum Action {
     Quit,
+    ForceQuit,
     Save,
+    SaveAs,
+    OpenFile,
+    Revert,
+    ResetView,
+    ExportView,
+    ExportLabels,
+    ImportLabels,
+    LoadReference,
+    MatchLoudness,
+    Bounce,
+    DuplicateAll,
     Play,
+    PlayLoop,
+    SelectionToLoop,
+    ToggleLoopAll,
+    Record,
     CursorLeft,
     CursorRight,
     CursorStart,
     CursorEnd,
+    JumpBack,
+    NavBack,
+    // Moves the cursor to the given percentage of the way through the file.
+    SeekPercent(u8),
     ZoomIn,
     ZoomOut,
+    ZoomInFast,
+    ZoomOutFast,
+    // Shows the given number of milliseconds centered on the cursor.
+    ZoomWidth(u64),
+    ZoomOneSecond,
+    CenterOnCursor,
     Select,
     SelectAll,
+    ClearSelection,
+    ExtendSelectionBeat,
+    ToggleSnap,
+    TapTempo,
+    MeasureInterval,
+    SnapToFrames,
     Amplify,
     Cut,
+    Copy,
+    Paste,
+    PasteAsNew,
+    ReplaceSelection,
+    DuplicateSelection,
     EffectLeft,
     EffectRight,
+    EffectLeftFine,
+    EffectRightFine,
+    EffectUp,
+    EffectDown,
+    Echo,
+    Gate,
+    ApplyEffect,
+    // Applies the effect to everything outside the selection instead.
+    ApplyToComplement,
+    ToggleEffectCompare,
+    MicroFade,
+    NudgeContentLeft,
+    NudgeContentRight,
+    ExtractAround,
+    PushEffect,
+    PopEffect,
+    InsertTone,
+    GoToSample,
+    CopyTimecode,
+    AddMarker,
+    CycleMarkerCategory,
+    SearchMarker,
+    SelectBetweenMarkers,
+    SelectSegment,
+    NextSegment,
+    PrevSegment,
+    SelectToNextMarker,
+    SelectToPrevMarker,
+    Downmix,
+    Upmix,
+    ToggleMonoMonitor,
+    ToggleClick,
+    ToggleLog,
+    CycleLogLevel,
+    ToggleInstructions,
+    ToggleSampleReadout,
+    ToggleSampleAxis,
+    ToggleLogTime,
+    ToggleScrub,
+    NormalizeFile,
+    Undo,
+    Redo,
+    ScrollLogUp,
+    ScrollLogDown,
+}
+
+// A built-in set of binds that the binds in the config are applied on top of.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    #[default]
+    Vim,
+    Basic,
+}
+
+impl Preset {
+    pub fn binds(self) -> BindMap<Action> {
+        match self {
+            Preset::Vim => vim_binds(),
+            Preset::Basic => basic_binds(),
+        }
+    }
+}
+
+// What Play does when the cursor is at the end of the file.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "lowercase")]
+pub enum PlayAtEnd {
+    #[default]
+    Nothing,
+    // Play from the start of the file.
+    Rewind,
+    // Play the last play_at_end_length milliseconds.
+    Last,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    // Which built-in binds to start from.
+    pub preset: Preset,
+    // Added to the preset's binds, replacing any for the same key.
+    #[serde(default)]
     pub binds: BindMap<Action>,
+    // How much EffectLeft/EffectRight change the effect parameter.
+    // EffectUp/EffectDown change the secondary parameter by effect_step.
+    pub effect_step: f32,
+    // How much EffectLeftFine/EffectRightFine change the effect parameter.
+    pub fine_effect_step: f32,
+    // Tempo used for beat-relative selection and gridlines.
+    pub bpm: Option<f64>,
+    // Step the cursor and selection between beat gridlines when bpm is set.
+    pub snap: bool,
+    // Move the ends of a cut to the nearest zero crossings to avoid clicks.
+    pub snap_cuts_to_zero: bool,
+    // Video frame rate that SnapToFrames rounds the selection to.
+    pub fps: f64,
+    // Factor by which ZoomInFast/ZoomOutFast change the width of the view.
+    pub fast_zoom: f64,
+    // Most times per second the screen is redrawn. 0 for no limit.
+    pub max_fps: f64,
+    // Milliseconds between checks of the playhead while playing. Lower is smoother but uses
+    // more CPU.
+    pub poll_interval_ms: u64,
+    // Scroll the view with every cursor move to keep the cursor in the middle, rather than
+    // only when the cursor reaches an edge.
+    pub center_cursor: bool,
+    // Draw fading lines at the playhead's last few positions, to make its motion easier
+    // to follow.
+    pub playhead_trail: bool,
+    // Peak level in dBFS that NormalizeFile scales the audio to.
+    pub normalize_dbfs: f32,
+    // Level in dBFS below which audio counts as silence when finding segments.
+    pub silence_threshold_db: f32,
+    // Maximum number of edits that can be undone.
+    pub undo_limit: usize,
+    // Soft-clip playback to ±1.0. Doesn't affect the saved audio.
+    pub limit_playback: bool,
+    // Milliseconds over which PlayLoop fades the end of the loop into the start.
+    pub loop_crossfade: u64,
+    // Milliseconds over which playback fades in when it starts and out at the end of the file,
+    // so monitoring doesn't click. Doesn't affect the saved audio. 0 disables the fades.
+    pub anti_click: u64,
+    // Resample pasted audio whose sample rate or channels differ from the file. When false,
+    // such a paste is refused.
+    pub conform_paste: bool,
+    // Ask before normalizing, downmixing, extracting, matching loudness or applying an effect
+    // to the whole file.
+    pub confirm_destructive: bool,
+    // Milliseconds over which MicroFade fades each edge of the selection.
+    pub micro_fade: u64,
+    // What Play does with the cursor at the end of the file.
+    pub play_at_end: PlayAtEnd,
+    // Milliseconds played when play_at_end is "last".
+    pub play_at_end_length: u64,
+    // Milliseconds kept either side of the cursor by ExtractAround.
+    pub extract_radius: u64,
+    // Advance the playhead smoothly between the output's position updates.
+    pub smooth_playhead: bool,
+    // Number of evenly spaced amplitude labels on the y-axis, including the ends.
+    pub y_ticks: usize,
+    // Label the x-axis in sample frames rather than seconds.
+    pub sample_axis: bool,
+    // Bit depth of saved WAV files: 16 or 24 bit integer, or 32 bit float.
+    pub wav_bits: WavBits,
+}
+
+// The default, vim-like binds.
+fn vim_binds() -> BindMap<Action> {
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
+    let alt = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+    BindMap::new([
+        // general
+        (key('s'), Binding::Action(vec![Action::Save])),
+        (key('S'), Binding::Action(vec![Action::SaveAs])),
+        (key('!'), Binding::Action(vec![Action::Revert])),
+        (key('E'), Binding::Action(vec![Action::ExportView])),
+        (alt('e'), Binding::Action(vec![Action::ExportLabels])),
+        (alt('i'), Binding::Action(vec![Action::ImportLabels])),
+        (alt('x'), Binding::Action(vec![Action::ExtractAround])),
+        (alt('l'), Binding::Action(vec![Action::ToggleLoopAll])),
+        (alt('c'), Binding::Action(vec![Action::CopyTimecode])),
+        (alt('p'), Binding::Action(vec![Action::PasteAsNew])),
+        (alt('m'), Binding::Action(vec![Action::CycleMarkerCategory])),
+        (alt('n'), Binding::Action(vec![Action::MatchLoudness])),
+        (alt('o'), Binding::Action(vec![Action::OpenFile])),
+        (alt('v'), Binding::Action(vec![Action::ToggleEffectCompare])),
+        (alt('b'), Binding::Action(vec![Action::Bounce])),
+        (alt('D'), Binding::Action(vec![Action::DuplicateAll])),
+        (alt('g'), Binding::Action(vec![Action::Gate])),
+        (alt('r'), Binding::Action(vec![Action::ResetView])),
+        (alt('t'), Binding::Action(vec![Action::ToggleLogTime])),
+        (alt('d'), Binding::Action(vec![Action::MeasureInterval])),
+        (key('F'), Binding::Action(vec![Action::LoadReference])),
+        (key('q'), Binding::Action(vec![Action::Quit])),
+        (key('Q'), Binding::Action(vec![Action::ForceQuit])),
+        (key('h'), Binding::Action(vec![Action::CursorLeft])),
+        (key('l'), Binding::Action(vec![Action::CursorRight])),
+        (key(' '), Binding::Action(vec![Action::Play])),
+        (key('P'), Binding::Action(vec![Action::PlayLoop])),
+        (key('V'), Binding::Action(vec![Action::SelectionToLoop])),
+        (key('R'), Binding::Action(vec![Action::Record])),
+        // zoom
+        (key('z'), Binding::Action(vec![Action::ZoomIn])),
+        (key('Z'), Binding::Action(vec![Action::ZoomOut])),
+        (alt('z'), Binding::Action(vec![Action::ZoomInFast])),
+        (alt('Z'), Binding::Action(vec![Action::ZoomOutFast])),
+        (key('C'), Binding::Action(vec![Action::CenterOnCursor])),
+        // selection
+        (key('v'), Binding::Action(vec![Action::Select])),
+        (key('%'), Binding::Action(vec![Action::SelectAll])),
+        (
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::empty()),
+            Binding::Action(vec![Action::ClearSelection]),
+        ),
+        (key('b'), Binding::Action(vec![Action::ExtendSelectionBeat])),
+        (key('B'), Binding::Action(vec![Action::ToggleSnap])),
+        (key('T'), Binding::Action(vec![Action::TapTempo])),
+        (key('|'), Binding::Action(vec![Action::SnapToFrames])),
+        // editing
+        (key('a'), Binding::Action(vec![Action::Amplify])),
+        (key('e'), Binding::Action(vec![Action::Echo])),
+        (key('f'), Binding::Action(vec![Action::MicroFade])),
+        (key('<'), Binding::Action(vec![Action::NudgeContentLeft])),
+        (key('>'), Binding::Action(vec![Action::NudgeContentRight])),
+        (key('x'), Binding::Action(vec![Action::Cut])),
+        (key('y'), Binding::Action(vec![Action::Copy])),
+        (key('p'), Binding::Action(vec![Action::Paste])),
+        (key('r'), Binding::Action(vec![Action::ReplaceSelection])),
+        (key('D'), Binding::Action(vec![Action::DuplicateSelection])),
+        (key('t'), Binding::Action(vec![Action::InsertTone])),
+        (key('N'), Binding::Action(vec![Action::NormalizeFile])),
+        (key('o'), Binding::Action(vec![Action::Undo])),
+        (key('O'), Binding::Action(vec![Action::Redo])),
+        // markers
+        (key('m'), Binding::Action(vec![Action::AddMarker])),
+        (key('L'), Binding::Action(vec![Action::ToggleLog])),
+        (alt('L'), Binding::Action(vec![Action::CycleLogLevel])),
+        (key('H'), Binding::Action(vec![Action::ToggleInstructions])),
+        (key('w'), Binding::Action(vec![Action::ToggleScrub])),
+        (key('k'), Binding::Action(vec![Action::ToggleClick])),
+        (key('#'), Binding::Action(vec![Action::ToggleSampleReadout])),
+        (key('+'), Binding::Action(vec![Action::PushEffect])),
+        (key('-'), Binding::Action(vec![Action::PopEffect])),
+        (key('X'), Binding::Action(vec![Action::ToggleSampleAxis])),
+        (key('K'), Binding::Action(vec![Action::ScrollLogUp])),
+        (key('J'), Binding::Action(vec![Action::ScrollLogDown])),
+        (key('/'), Binding::Action(vec![Action::SearchMarker])),
+        (key('`'), Binding::Action(vec![Action::JumpBack])),
+        (key(','), Binding::Action(vec![Action::NavBack])),
+        (
+            key('M'),
+            Binding::Action(vec![Action::SelectBetweenMarkers]),
+        ),
+        (key('}'), Binding::Action(vec![Action::SelectToNextMarker])),
+        (key('{'), Binding::Action(vec![Action::SelectToPrevMarker])),
+        (key('W'), Binding::Action(vec![Action::SelectSegment])),
+        (key(')'), Binding::Action(vec![Action::NextSegment])),
+        (key('('), Binding::Action(vec![Action::PrevSegment])),
+        // g navigation chains
+        (
+            key('g'),
+            Binding::Chain(BindMap::new([
+                (key('s'), Binding::Action(vec![Action::CursorStart])),
+                (key('l'), Binding::Action(vec![Action::CursorEnd])),
+                (key('i'), Binding::Action(vec![Action::GoToSample])),
+                (key('1'), Binding::Action(vec![Action::SeekPercent(10)])),
+                (key('2'), Binding::Action(vec![Action::SeekPercent(20)])),
+                (key('3'), Binding::Action(vec![Action::SeekPercent(30)])),
+                (key('4'), Binding::Action(vec![Action::SeekPercent(40)])),
+                (key('5'), Binding::Action(vec![Action::SeekPercent(50)])),
+                (key('6'), Binding::Action(vec![Action::SeekPercent(60)])),
+                (key('7'), Binding::Action(vec![Action::SeekPercent(70)])),
+                (key('8'), Binding::Action(vec![Action::SeekPercent(80)])),
+                (key('9'), Binding::Action(vec![Action::SeekPercent(90)])),
+            ])),
+        ),
+        // zoom presets
+        (
+            key('d'),
+            Binding::Chain(BindMap::new([
+                (key('1'), Binding::Action(vec![Action::ZoomWidth(100)])),
+                (key('2'), Binding::Action(vec![Action::ZoomOneSecond])),
+                (key('3'), Binding::Action(vec![Action::ZoomWidth(10_000)])),
+            ])),
+        ),
+        // channels
+        (
+            key('c'),
+            Binding::Chain(BindMap::new([
+                (key('m'), Binding::Action(vec![Action::Downmix])),
+                (key('s'), Binding::Action(vec![Action::Upmix])),
+                (key('M'), Binding::Action(vec![Action::ToggleMonoMonitor])),
+            ])),
+        ),
+        // effects
+        (key('u'), Binding::Action(vec![Action::EffectLeft])),
+        (key('i'), Binding::Action(vec![Action::EffectRight])),
+        (key('U'), Binding::Action(vec![Action::EffectLeftFine])),
+        (key('I'), Binding::Action(vec![Action::EffectRightFine])),
+        (key('['), Binding::Action(vec![Action::EffectDown])),
+        (key(']'), Binding::Action(vec![Action::EffectUp])),
+        (
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()),
+            Binding::Action(vec![Action::ApplyEffect]),
+        ),
+        (
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+            Binding::Action(vec![Action::ApplyToComplement]),
+        ),
+    ])
+}
+
+// Binds for arrow keys and common shortcuts, for those unfamiliar with vim.
+fn basic_binds() -> BindMap<Action> {
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
+    let code = |code| KeyEvent::new(code, KeyModifiers::empty());
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let shift = |code| KeyEvent::new(code, KeyModifiers::SHIFT);
+    BindMap::new([
+        // general
+        (ctrl('s'), Binding::Action(vec![Action::Save])),
+        (key('q'), Binding::Action(vec![Action::Quit])),
+        (key(' '), Binding::Action(vec![Action::Play])),
+        (
+            code(KeyCode::Left),
+            Binding::Action(vec![Action::CursorLeft]),
+        ),
+        (
+            code(KeyCode::Right),
+            Binding::Action(vec![Action::CursorRight]),
+        ),
+        (
+            code(KeyCode::Home),
+            Binding::Action(vec![Action::CursorStart]),
+        ),
+        (code(KeyCode::End), Binding::Action(vec![Action::CursorEnd])),
+        // zoom
+        (key('+'), Binding::Action(vec![Action::ZoomIn])),
+        (key('='), Binding::Action(vec![Action::ZoomIn])),
+        (key('-'), Binding::Action(vec![Action::ZoomOut])),
+        // selection
+        (key('s'), Binding::Action(vec![Action::Select])),
+        (ctrl('a'), Binding::Action(vec![Action::SelectAll])),
+        (
+            code(KeyCode::Esc),
+            Binding::Action(vec![Action::ClearSelection]),
+        ),
+        // editing
+        (key('a'), Binding::Action(vec![Action::Amplify])),
+        (ctrl('x'), Binding::Action(vec![Action::Cut])),
+        (code(KeyCode::Delete), Binding::Action(vec![Action::Cut])),
+        (ctrl('c'), Binding::Action(vec![Action::Copy])),
+        (ctrl('v'), Binding::Action(vec![Action::Paste])),
+        (ctrl('z'), Binding::Action(vec![Action::Undo])),
+        (ctrl('y'), Binding::Action(vec![Action::Redo])),
+        (key('m'), Binding::Action(vec![Action::AddMarker])),
+        // effects
+        (
+            code(KeyCode::Down),
+            Binding::Action(vec![Action::EffectLeft]),
+        ),
+        (
+            code(KeyCode::Up),
+            Binding::Action(vec![Action::EffectRight]),
+        ),
+        (
+            shift(KeyCode::Down),
+            Binding::Action(vec![Action::EffectLeftFine]),
+        ),
+        (
+            shift(KeyCode::Up),
+            Binding::Action(vec![Action::EffectRightFine]),
+        ),
+        (
+            code(KeyCode::PageDown),
+            Binding::Action(vec![Action::EffectDown]),
+        ),
+        (
+            code(KeyCode::PageUp),
+            Binding::Action(vec![Action::EffectUp]),
+        ),
+        (
+            code(KeyCode::Enter),
+            Binding::Action(vec![Action::ApplyEffect]),
+        ),
+    ])
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty());
         Self {
-            binds: BindMap::new([
-                // general
-                (key('s'), Binding::Action(vec![Action::Save])),
-                (key('q'), Binding::Action(vec![Action::Quit])),
-                (key('h'), Binding::Action(vec![Action::CursorLeft])),
-                (key('l'), Binding::Action(vec![Action::CursorRight])),
-                (key(' '), Binding::Action(vec![Action::Play])),
-                // zoom
-                (key('z'), Binding::Action(vec![Action::ZoomIn])),
-                (key('Z'), Binding::Action(vec![Action::ZoomOut])),
-                // selection
-                (key('v'), Binding::Action(vec![Action::Select])),
-                (key('%'), Binding::Action(vec![Action::SelectAll])),
-                // editing
-                (key('a'), Binding::Action(vec![Action::Amplify])),
-                (key('x'), Binding::Action(vec![Action::Cut])),
-                // g navigation chains
-                (
-                    key('g'),
-                    Binding::Chain(BindMap::new([
-                        (key('s'), Binding::Action(vec![Action::CursorStart])),
-                        (key('l'), Binding::Action(vec![Action::CursorEnd])),
-                    ])),
-                ),
-                // effects
-                (key('u'), Binding::Action(vec![Action::EffectLeft])),
-                (key('i'), Binding::Action(vec![Action::EffectRight])),
-            ]),
+            preset: Preset::Vim,
+            binds: Preset::Vim.binds(),
+            effect_step: 0.1,
+            fine_effect_step: 0.01,
+            bpm: None,
+            snap: false,
+            snap_cuts_to_zero: false,
+            fps: 30.0,
+            max_fps: 30.0,
+            poll_interval_ms: 50,
+            center_cursor: false,
+            playhead_trail: false,
+            fast_zoom: 4.0,
+            normalize_dbfs: -1.0,
+            silence_threshold_db: -40.0,
+            undo_limit: 50,
+            limit_playback: false,
+            loop_crossfade: 10,
+            anti_click: 5,
+            micro_fade: 5,
+            play_at_end: PlayAtEnd::Nothing,
+            play_at_end_length: 3000,
+            extract_radius: 2000,
+            conform_paste: true,
+            confirm_destructive: true,
+            smooth_playhead: true,
+            y_ticks: 3,
+            sample_axis: false,
+            wav_bits: WavBits::Int16,
         }
     }
 }
 
 impl Config {
     pub fn read(s: &str) -> Result<Config> {
-        let c: Self = toml::from_str(s)?;
+        let mut c: Self = toml::from_str(s)?;
+        let overrides = std::mem::replace(&mut c.binds, c.preset.binds());
+        c.binds.overlay(overrides);
         Ok(c)
     }
+
+    // Loads config.toml from the given config directory, using the defaults if there is none.
+    // Fields missing from the file keep their default values.
+    pub fn discover(dir: &Path) -> Result<Config> {
+        let path = dir.join("config.toml");
+        match std::fs::read_to_string(&path) {
+            Ok(s) => {
+                log::info!("Loading config from {path:?}");
+                log::trace!("Read config:\n {s:?}");
+                Self::read(&s)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No config at {path:?}, using defaults");
+                Ok(Config::default())
+            }
+            Err(err) => {
+                bail!("Failed to read {path:?}: {err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_discover() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::discover(dir.path()).unwrap();
+        assert_eq!(config.effect_step, 0.1);
+
+        std::fs::write(
+            dir.path().join("config.toml"),
+            "effect_step = 0.5\nbpm = 120",
+        )
+        .unwrap();
+        let config = Config::discover(dir.path()).unwrap();
+        assert_eq!(config.effect_step, 0.5);
+        assert_eq!(config.bpm, Some(120.0));
+        assert_eq!(config.fine_effect_step, 0.01);
+    }
+
+    #[test]
+    fn test_discover_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("config.toml"), "effect_step = \"loud\"").unwrap();
+        assert!(Config::discover(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_seek_percent_bind() {
+        let config = Config::read("[binds]\nG = { seek_percent = 25 }").unwrap();
+        let key = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::empty());
+        let mut binds = crate::binds::Binds::new(config.binds);
+        assert_eq!(
+            binds.apply(key),
+            Some(&Binding::Action(vec![Action::SeekPercent(25)]))
+        );
+    }
+
+    #[test]
+    fn test_play_at_end() {
+        assert_eq!(Config::default().play_at_end, PlayAtEnd::Nothing);
+        assert_eq!(
+            Config::read("play_at_end = \"rewind\"")
+                .unwrap()
+                .play_at_end,
+            PlayAtEnd::Rewind
+        );
+        assert_eq!(
+            Config::read("play_at_end = \"last\"").unwrap().play_at_end,
+            PlayAtEnd::Last
+        );
+        assert!(Config::read("play_at_end = \"loop\"").is_err());
+    }
+
+    #[test]
+    fn test_wav_bits() {
+        assert_eq!(Config::default().wav_bits, WavBits::Int16);
+        assert_eq!(
+            Config::read("wav_bits = 24").unwrap().wav_bits,
+            WavBits::Int24
+        );
+        assert_eq!(
+            Config::read("wav_bits = 32").unwrap().wav_bits,
+            WavBits::Float32
+        );
+        let err = Config::read("wav_bits = 8").unwrap_err().to_string();
+        assert!(err.contains("unsupported wav_bits 8"), "{err}");
+    }
+
+    #[test]
+    fn test_presets() {
+        let apply = |config: &str, key: KeyEvent| {
+            let config = Config::read(config).unwrap();
+            match crate::binds::Binds::new(config.binds).apply(key) {
+                Some(Binding::Action(actions)) => Some(actions.clone()),
+                _ => None,
+            }
+        };
+        let right = KeyEvent::new(KeyCode::Right, KeyModifiers::empty());
+        let l = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::empty());
+        let undo = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+
+        assert_eq!(
+            Config::read("preset = \"vim\"").unwrap().binds,
+            Config::default().binds
+        );
+        assert_eq!(apply("", l), Some(vec![Action::CursorRight]));
+        assert_eq!(apply("", right), None);
+
+        let basic = "preset = \"basic\"";
+        assert_eq!(Config::read(basic).unwrap().preset, Preset::Basic);
+        assert_eq!(apply(basic, right), Some(vec![Action::CursorRight]));
+        assert_eq!(apply(basic, undo), Some(vec![Action::Undo]));
+        assert_eq!(apply(basic, l), None);
+
+        // explicit binds are added on top of the preset
+        let config = "preset = \"basic\"\n[binds]\nl = \"cursor_end\"";
+        assert_eq!(apply(config, l), Some(vec![Action::CursorEnd]));
+        assert_eq!(apply(config, right), Some(vec![Action::CursorRight]));
+
+        assert!(Config::read("preset = \"emacs\"").is_err());
+    }
 }
@@ -0,0 +1,846 @@
+use std::time::Duration;
+
+// Audio held as interleaved samples, independent of any playback or UI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Audio {
+    pub fn new(channels: u16, sample_rate: u32, samples: Vec<f32>) -> Self {
+        Self {
+            channels,
+            sample_rate,
+            samples,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    pub fn duration(&self) -> Duration {
+        let nanos = self.frames() as u64 * 1_000_000_000 / self.sample_rate as u64;
+        Duration::from_nanos(nanos)
+    }
+
+    // Returns the index of the first sample in the frame nearest to pos, clamped to the end.
+    pub fn index(&self, pos: Duration) -> usize {
+        (nearest_frame(pos, self.sample_rate) as usize).min(self.frames()) * self.channels as usize
+    }
+
+    pub fn slice(&self, start: Duration, end: Duration) -> &[f32] {
+        let (start, end) = (self.index(start), self.index(end));
+        &self.samples[start..end.max(start)]
+    }
+
+    // Replaces the audio between start and end with the given samples.
+    pub fn splice(
+        &mut self,
+        start: Duration,
+        end: Duration,
+        samples: impl IntoIterator<Item = f32>,
+    ) {
+        let (start, end) = (self.index(start), self.index(end));
+        self.samples.splice(start..end.max(start), samples);
+    }
+
+    pub fn cut(&mut self, start: Duration, end: Duration) {
+        self.splice(start, end, []);
+    }
+
+    pub fn insert(&mut self, at: Duration, samples: impl IntoIterator<Item = f32>) {
+        self.splice(at, at, samples);
+    }
+
+    pub fn apply(&mut self, start: Duration, end: Duration, effect: &Effect) {
+        let (start, end) = (self.index(start), self.index(end));
+        let (channels, sample_rate) = (self.channels, self.sample_rate);
+        effect.apply(
+            &mut self.samples[start..end.max(start)],
+            channels,
+            sample_rate,
+        );
+    }
+
+    // Returns the position of the frame nearest to pos at which the audio crosses zero,
+    // searching up to ZERO_CROSSING_RANGE either side, or pos if there is none. Channels are
+    // averaged, and of the two frames either side of a crossing the quieter is used.
+    pub fn nearest_zero_crossing(&self, pos: Duration) -> Duration {
+        let channels = self.channels as usize;
+        let frames = self.frames();
+        let value = |f: usize| {
+            let frame = &self.samples[f * channels..(f + 1) * channels];
+            frame.iter().sum::<f32>() / channels as f32
+        };
+        let crossing = |f: usize| {
+            if f == 0 || f >= frames {
+                return None;
+            }
+            let (prev, next) = (value(f - 1), value(f));
+            if next == 0.0 {
+                Some(f)
+            } else if prev.signum() != next.signum() {
+                Some(if prev.abs() < next.abs() { f - 1 } else { f })
+            } else {
+                None
+            }
+        };
+        let at = self.index(pos) / channels;
+        let range = (ZERO_CROSSING_RANGE.as_secs_f64() * self.sample_rate as f64) as usize;
+        let found = (0..=range)
+            .find_map(|d| crossing(at + d).or_else(|| at.checked_sub(d).and_then(crossing)));
+        match found {
+            Some(frame) => Duration::from_nanos(
+                (frame as u128 * 1_000_000_000 / self.sample_rate as u128) as u64,
+            ),
+            None => pos,
+        }
+    }
+
+    // Returns the (start, end) of each stretch of audio louder than threshold, in order. Quiet
+    // gaps shorter than SEGMENT_GAP, like the zero crossings of a wave, don't split a segment.
+    pub fn segments(&self, threshold: f32) -> Vec<(Duration, Duration)> {
+        let channels = self.channels as usize;
+        let gap = (SEGMENT_GAP.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        let time = |frame: usize| {
+            Duration::from_nanos((frame as u128 * 1_000_000_000 / self.sample_rate as u128) as u64)
+        };
+        let mut segments = vec![];
+        // (first, last) loud frame of the segment being built
+        let mut current: Option<(usize, usize)> = None;
+        for (i, frame) in self.samples.chunks_exact(channels).enumerate() {
+            if !frame.iter().any(|v| v.abs() > threshold) {
+                continue;
+            }
+            current = match current {
+                Some((first, last)) if i - last <= gap => Some((first, i)),
+                Some((first, last)) => {
+                    segments.push((time(first), time(last + 1)));
+                    Some((i, i))
+                }
+                None => Some((i, i)),
+            };
+        }
+        if let Some((first, last)) = current {
+            segments.push((time(first), time(last + 1)));
+        }
+        segments
+    }
+
+    // Scales the audio between start and end so its peak is at the given level.
+    // Returns the applied gain, or None if the region is silent.
+    pub fn normalize(&mut self, start: Duration, end: Duration, peak: f32) -> Option<f32> {
+        let (start, end) = (self.index(start), self.index(end));
+        let samples = &mut self.samples[start..end.max(start)];
+        let max = samples.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+        if max == 0.0 {
+            return None;
+        }
+        let gain = peak / max;
+        samples.iter_mut().for_each(|v| *v *= gain);
+        Some(gain)
+    }
+
+    // Returns the root mean square of the samples between start and end, or None if there
+    // are none.
+    pub fn rms(&self, start: Duration, end: Duration) -> Option<f32> {
+        let samples = self.slice(start, end);
+        if samples.is_empty() {
+            return None;
+        }
+        let sum: f64 = samples.iter().map(|&v| (v as f64).powi(2)).sum();
+        Some((sum / samples.len() as f64).sqrt() as f32)
+    }
+
+    // Moves the audio between start and end later by frames, or earlier if negative, filling
+    // the vacated side with silence. Audio moved past either end of the region is dropped.
+    pub fn shift(&mut self, start: Duration, end: Duration, frames: isize) {
+        let channels = self.channels as usize;
+        let (start, end) = (self.index(start), self.index(end));
+        let samples = &mut self.samples[start..end.max(start)];
+        let offset = (frames.unsigned_abs() * channels).min(samples.len());
+        if frames >= 0 {
+            samples.rotate_right(offset);
+            samples[..offset].fill(0.0);
+        } else {
+            samples.rotate_left(offset);
+            let len = samples.len();
+            samples[len - offset..].fill(0.0);
+        }
+    }
+
+    // Fades the audio between start and end in over the first length and out over the last,
+    // removing clicks at its edges. Each fade is at most half the region.
+    pub fn micro_fade(&mut self, start: Duration, end: Duration, length: Duration) {
+        let channels = self.channels as usize;
+        let fade = (length.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        let (start, end) = (self.index(start), self.index(end));
+        let samples = &mut self.samples[start..end.max(start)];
+        let frames = samples.len() / channels;
+        let fade = fade.min(frames / 2);
+        for i in 0..fade {
+            let gain = i as f32 / fade as f32;
+            let tail = frames - 1 - i;
+            for c in 0..channels {
+                samples[i * channels + c] *= gain;
+                samples[tail * channels + c] *= gain;
+            }
+        }
+    }
+
+    // Prepares the audio to be played on repeat by fading its tail into its head.
+    // The tail is removed, so the end flows into the start without a click.
+    pub fn crossfade_loop(&mut self, duration: Duration) {
+        let channels = self.channels as usize;
+        let frames = self.frames();
+        let fade = (duration.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        let fade = fade.min(frames / 2);
+        if fade == 0 {
+            return;
+        }
+        let tail = frames - fade;
+        for i in 0..fade {
+            let t = i as f32 / fade as f32;
+            for c in 0..channels {
+                let head = self.samples[i * channels + c];
+                let end = self.samples[(tail + i) * channels + c];
+                self.samples[i * channels + c] = head * t + end * (1.0 - t);
+            }
+        }
+        self.samples.truncate(tail * channels);
+    }
+
+    // Mixes a short click into every channel at each multiple of beat, where the audio starts
+    // offset into the beat grid.
+    pub fn add_clicks(&mut self, offset: Duration, beat: Duration) {
+        if beat.is_zero() {
+            return;
+        }
+        let channels = self.channels as usize;
+        let frames = self.frames();
+        let click_frames = (CLICK_LENGTH.as_secs_f64() * self.sample_rate as f64) as usize;
+        let mut pos = beat * offset.as_nanos().div_ceil(beat.as_nanos()) as u32;
+        while pos < offset + self.duration() {
+            let start = self.index(pos - offset) / channels;
+            for i in 0..click_frames.min(frames - start) {
+                let t = i as f32 / self.sample_rate as f32;
+                let decay = 1.0 - i as f32 / click_frames as f32;
+                let v = 0.5 * decay * (2.0 * std::f32::consts::PI * CLICK_FREQ * t).cos();
+                for c in 0..channels {
+                    self.samples[(start + i) * channels + c] += v;
+                }
+            }
+            pos += beat;
+        }
+    }
+
+    // Converts to the given sample rate using linear interpolation.
+    pub fn resample(&self, sample_rate: u32) -> Audio {
+        let channels = self.channels as usize;
+        let frames = self.frames();
+        if sample_rate == self.sample_rate || frames == 0 {
+            return Audio::new(self.channels, sample_rate, self.samples.clone());
+        }
+        let ratio = self.sample_rate as f64 / sample_rate as f64;
+        let new_frames = (frames as f64 / ratio).round() as usize;
+        let mut samples = Vec::with_capacity(new_frames * channels);
+        for frame in 0..new_frames {
+            let pos = frame as f64 * ratio;
+            let i = (pos as usize).min(frames - 1);
+            let next = (i + 1).min(frames - 1);
+            let t = (pos - i as f64) as f32;
+            for c in 0..channels {
+                let a = self.samples[i * channels + c];
+                let b = self.samples[next * channels + c];
+                samples.push(a + (b - a) * t);
+            }
+        }
+        Audio::new(self.channels, sample_rate, samples)
+    }
+
+    // Averages all channels into one.
+    pub fn downmix(&self) -> Audio {
+        let channels = self.channels as usize;
+        let samples = self
+            .samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Audio::new(1, self.sample_rate, samples)
+    }
+
+    // Duplicates a single channel into two.
+    pub fn upmix(&self) -> Audio {
+        let samples = self.samples.iter().flat_map(|&v| [v, v]).collect();
+        Audio::new(2, self.sample_rate, samples)
+    }
+}
+
+pub enum Effect {
+    Amplify {
+        amount: f32,
+    },
+    // Repeats the audio after delay seconds, each repeat scaled by feedback.
+    Echo {
+        delay: f32,
+        feedback: f32,
+    },
+    // Silences audio quieter than threshold_db, opening over attack seconds and closing
+    // over release seconds.
+    Gate {
+        threshold_db: f32,
+        attack: f32,
+        release: f32,
+    },
+}
+
+impl Effect {
+    // Adjusts the primary parameter: the gain of Amplify, the delay of Echo, or the threshold
+    // of Gate.
+    pub fn increase(&mut self, delta: f32) {
+        match self {
+            Effect::Amplify { amount } => *amount = (*amount + delta).clamp(0.0, MAX_AMPLIFY),
+            Effect::Echo { delay, .. } => *delay = (*delay + delta).clamp(0.0, MAX_ECHO_DELAY),
+            // a step of 0.1 moves the threshold by 1dB
+            Effect::Gate { threshold_db, .. } => {
+                *threshold_db = (*threshold_db + delta * 10.0).clamp(MIN_GATE_THRESHOLD_DB, 0.0)
+            }
+        }
+    }
+
+    // Adjusts the secondary parameter, if the effect has one.
+    pub fn increase_secondary(&mut self, delta: f32) {
+        match self {
+            Effect::Amplify { .. } | Effect::Gate { .. } => {}
+            Effect::Echo { feedback, .. } => {
+                *feedback = (*feedback + delta).clamp(0.0, MAX_ECHO_FEEDBACK)
+            }
+        }
+    }
+
+    // Whether a parameter is at the end of its range, so increase may have no effect.
+    pub fn pinned(&self) -> bool {
+        match *self {
+            Effect::Amplify { amount } => amount <= 0.0 || amount >= MAX_AMPLIFY,
+            Effect::Echo { delay, feedback } => {
+                delay <= 0.0
+                    || delay >= MAX_ECHO_DELAY
+                    || feedback <= 0.0
+                    || feedback >= MAX_ECHO_FEEDBACK
+            }
+            Effect::Gate { threshold_db, .. } => {
+                threshold_db <= MIN_GATE_THRESHOLD_DB || threshold_db >= 0.0
+            }
+        }
+    }
+
+    // Returns the names of the primary and secondary parameters.
+    pub fn params(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            Effect::Amplify { .. } => ("gain", None),
+            Effect::Echo { .. } => ("delay", Some("feedback")),
+            Effect::Gate { .. } => ("threshold", None),
+        }
+    }
+
+    pub fn apply(&self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        match self {
+            Effect::Amplify { amount } => samples.iter_mut().for_each(|v| *v *= amount),
+            Effect::Echo { delay, feedback } => {
+                let offset = (delay * sample_rate as f32).round() as usize * channels as usize;
+                if offset == 0 {
+                    return;
+                }
+                // earlier samples already include their echoes, so repeats feed back
+                for i in offset..samples.len() {
+                    samples[i] += samples[i - offset] * feedback;
+                }
+            }
+            Effect::Gate {
+                threshold_db,
+                attack,
+                release,
+            } => {
+                let threshold = 10f32.powf(threshold_db / 20.0);
+                // per-frame factors for exponential smoothing over the given time
+                let rate = sample_rate as f32;
+                let smoothing = |secs: f32| {
+                    if secs > 0.0 {
+                        1.0 - (-1.0 / (secs * rate)).exp()
+                    } else {
+                        1.0
+                    }
+                };
+                let (open, close) = (smoothing(*attack), smoothing(*release));
+                let decay = 1.0 - close;
+                let mut envelope = 0.0f32;
+                let mut gain = 1.0f32;
+                for frame in samples.chunks_mut(channels as usize) {
+                    let level = frame.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+                    // follow peaks immediately, but hold through zero crossings
+                    envelope = level.max(envelope * decay);
+                    if envelope >= threshold {
+                        gain += (1.0 - gain) * open;
+                    } else {
+                        gain -= gain * close;
+                    }
+                    frame.iter_mut().for_each(|v| *v *= gain);
+                }
+            }
+        }
+    }
+
+    // Returns the effect name and current parameter for display.
+    pub fn describe(&self) -> String {
+        match self {
+            Effect::Amplify { amount } if *amount <= 0.0 => "Amplify -inf dB".into(),
+            Effect::Amplify { amount } => format!("Amplify {:+.1}dB", 20.0 * amount.log10()),
+            Effect::Echo { delay, feedback } => format!(
+                "Echo {:.0}ms {:.0}% feedback",
+                delay * 1000.0,
+                feedback * 100.0
+            ),
+            Effect::Gate { threshold_db, .. } => format!("Gate {threshold_db:.0}dB"),
+        }
+    }
+}
+
+// How far nearest_zero_crossing looks for a crossing.
+const ZERO_CROSSING_RANGE: Duration = Duration::from_millis(10);
+
+// Upper limits of effect parameters. Amplify goes up to +24dB, and echoes with feedback of
+// 1 or more would grow without end.
+const MAX_AMPLIFY: f32 = 16.0;
+const MAX_ECHO_DELAY: f32 = 2.0;
+const MAX_ECHO_FEEDBACK: f32 = 0.95;
+const MIN_GATE_THRESHOLD_DB: f32 = -80.0;
+
+// Shortest silence that separates two segments.
+const SEGMENT_GAP: Duration = Duration::from_millis(50);
+
+// Length and pitch of the clicks mixed in by add_clicks.
+const CLICK_LENGTH: Duration = Duration::from_millis(5);
+const CLICK_FREQ: f32 = 2000.0;
+
+// Returns the index of the frame nearest to pos, which is where edits at pos take effect.
+pub fn nearest_frame(pos: Duration, sample_rate: u32) -> u128 {
+    (pos.as_nanos() * sample_rate as u128 + 500_000_000) / 1_000_000_000
+}
+
+// Level above which soft_clip starts compressing.
+const SOFT_CLIP_KNEE: f32 = 0.9;
+
+// Smoothly limits a sample to ±1.0, leaving quieter samples untouched.
+pub fn soft_clip(v: f32) -> f32 {
+    let mag = v.abs();
+    if mag <= SOFT_CLIP_KNEE {
+        return v;
+    }
+    let range = 1.0 - SOFT_CLIP_KNEE;
+    v.signum() * (SOFT_CLIP_KNEE + range * ((mag - SOFT_CLIP_KNEE) / range).tanh())
+}
+
+// Generates a sine wave of the given frequency, duplicated across channels.
+// A frequency of 0 produces silence.
+pub fn tone(freq: f32, duration: Duration, channels: u16, sample_rate: u32) -> Vec<f32> {
+    let frames = (duration.as_secs_f64() * sample_rate as f64) as usize;
+    (0..frames)
+        .flat_map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let v = 0.5 * (2.0 * std::f32::consts::PI * freq * t).sin();
+            std::iter::repeat_n(v, channels as usize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    // 10 frames at 100hz, so each frame is 10ms
+    fn audio() -> Audio {
+        Audio::new(1, 100, (0..10).map(|i| i as f32 / 10.0).collect())
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(audio().duration(), ms(100));
+        assert_eq!(Audio::new(2, 100, vec![0.0; 20]).duration(), ms(100));
+        assert_eq!(Audio::new(1, 100, vec![]).duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_index() {
+        let stereo = Audio::new(2, 100, vec![0.0; 20]);
+        assert_eq!(stereo.index(ms(0)), 0);
+        assert_eq!(stereo.index(ms(30)), 6);
+        assert_eq!(stereo.index(ms(34)), 6);
+        assert_eq!(stereo.index(ms(36)), 8);
+        assert_eq!(stereo.index(ms(500)), 20);
+    }
+
+    #[test]
+    fn test_cut() {
+        let mut audio = audio();
+        audio.cut(ms(20), ms(50));
+        assert_eq!(audio.samples, [0.0, 0.1, 0.5, 0.6, 0.7, 0.8, 0.9]);
+
+        // past the end
+        audio.cut(ms(50), ms(500));
+        assert_eq!(audio.samples, [0.0, 0.1, 0.5, 0.6, 0.7]);
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut audio = audio();
+        audio.insert(ms(90), [1.0, 1.0]);
+        assert_eq!(
+            audio.samples,
+            [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 1.0, 1.0, 0.9]
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut audio = audio();
+        audio.apply(ms(70), ms(100), &Effect::Amplify { amount: 2.0 });
+        assert_eq!(
+            audio.samples,
+            [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 1.4, 1.6, 1.8]
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(Effect::Amplify { amount: 1.0 }.describe(), "Amplify +0.0dB");
+        assert_eq!(Effect::Amplify { amount: 2.0 }.describe(), "Amplify +6.0dB");
+        assert_eq!(Effect::Amplify { amount: 0.5 }.describe(), "Amplify -6.0dB");
+        assert_eq!(
+            Effect::Amplify { amount: 0.0 }.describe(),
+            "Amplify -inf dB"
+        );
+        assert_eq!(
+            Effect::Echo {
+                delay: 0.25,
+                feedback: 0.5
+            }
+            .describe(),
+            "Echo 250ms 50% feedback"
+        );
+        assert_eq!(
+            Effect::Gate {
+                threshold_db: -40.0,
+                attack: 0.005,
+                release: 0.05
+            }
+            .describe(),
+            "Gate -40dB"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut audio = Audio::new(1, 100, vec![0.1, -0.25, 0.2, 0.5]);
+        assert_eq!(audio.normalize(ms(0), ms(30), 1.0), Some(4.0));
+        assert_eq!(audio.samples, [0.4, -1.0, 0.8, 0.5]);
+
+        let mut silent = Audio::new(1, 100, vec![0.0; 4]);
+        assert_eq!(silent.normalize(ms(0), ms(40), 1.0), None);
+    }
+
+    #[test]
+    fn test_rms() {
+        let audio = Audio::new(1, 100, vec![0.5, -0.5, 1.0, -1.0]);
+        assert_eq!(audio.rms(ms(0), ms(20)), Some(0.5));
+        assert_eq!(audio.rms(ms(20), ms(40)), Some(1.0));
+        assert_eq!(audio.rms(ms(10), ms(30)), Some(0.625f32.sqrt()));
+        assert_eq!(audio.rms(ms(40), ms(50)), None);
+    }
+
+    #[test]
+    fn test_crossfade_loop() {
+        let mut audio = audio();
+        audio.crossfade_loop(ms(40));
+        // the last 4 frames are faded into the first 4
+        let expected = [
+            0.6,
+            0.1 * 0.25 + 0.7 * 0.75,
+            0.2 * 0.5 + 0.8 * 0.5,
+            0.3 * 0.75 + 0.9 * 0.25,
+        ];
+        assert_eq!(audio.samples.len(), 6);
+        for (a, b) in audio.samples.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-6, "{a} != {b}");
+        }
+        assert_eq!(audio.samples[4..], [0.4, 0.5]);
+
+        // no longer than half the audio
+        let mut audio = Audio::new(1, 100, vec![0.0, 1.0]);
+        audio.crossfade_loop(ms(1000));
+        assert_eq!(audio.samples, [1.0]);
+    }
+
+    #[test]
+    fn test_resample() {
+        let audio = Audio::new(2, 100, vec![0.0, 1.0, 0.5, 0.0, 1.0, -1.0]);
+
+        let up = audio.resample(200);
+        assert_eq!(up.sample_rate, 200);
+        assert_eq!(up.duration(), audio.duration());
+        assert_eq!(
+            up.samples,
+            [0.0, 1.0, 0.25, 0.5, 0.5, 0.0, 0.75, -0.5, 1.0, -1.0, 1.0, -1.0]
+        );
+
+        let down = up.resample(100);
+        assert_eq!(down.samples, audio.samples);
+    }
+
+    #[test]
+    fn test_downmix() {
+        let audio = Audio::new(2, 100, vec![1.0, 0.0, 0.5, 0.5, -1.0, 0.0]);
+        assert_eq!(audio.downmix(), Audio::new(1, 100, vec![0.5, 0.5, -0.5]));
+    }
+
+    #[test]
+    fn test_upmix() {
+        let audio = Audio::new(1, 100, vec![0.5, -0.5]);
+        assert_eq!(
+            audio.upmix(),
+            Audio::new(2, 100, vec![0.5, 0.5, -0.5, -0.5])
+        );
+    }
+
+    #[test]
+    fn test_soft_clip() {
+        assert_eq!(soft_clip(0.5), 0.5);
+        assert_eq!(soft_clip(-0.9), -0.9);
+        assert!(soft_clip(1.0) < 1.0 && soft_clip(1.0) > 0.9);
+        assert!(soft_clip(4.0) <= 1.0);
+        assert!(soft_clip(-4.0) >= -1.0);
+        assert!(soft_clip(2.0) > soft_clip(1.5));
+    }
+
+    #[test]
+    fn test_tone() {
+        let samples = tone(25.0, ms(40), 2, 100);
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples[0], samples[1]);
+        assert!((samples[2] - 0.5).abs() < 1e-6);
+        assert!(tone(0.0, ms(40), 1, 100).iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn test_add_clicks() {
+        let mut audio = Audio::new(2, 1000, vec![0.0; 2 * 100]);
+        // beats every 30ms, starting 10ms into the grid
+        audio.add_clicks(ms(10), ms(30));
+        let clicks: Vec<_> = (0..audio.frames())
+            .filter(|i| audio.samples[i * 2] == 0.5)
+            .collect();
+        assert_eq!(clicks, [20, 50, 80]);
+        // each click is mixed into every channel and decays
+        assert_eq!(audio.samples[20 * 2 + 1], 0.5);
+        assert!(audio.samples[24 * 2].abs() < 0.5);
+        assert_eq!(audio.samples[25 * 2], 0.0);
+
+        // a click near the end is cut short
+        let mut audio = Audio::new(1, 1000, vec![0.0; 12]);
+        audio.add_clicks(ms(0), ms(10));
+        assert_eq!(audio.samples[0], 0.5);
+        assert_eq!(audio.samples[10], 0.5);
+        assert_eq!(audio.samples.len(), 12);
+    }
+
+    #[test]
+    fn test_nearest_zero_crossing() {
+        let audio = Audio::new(1, 1000, vec![0.5, 0.3, -0.1, -0.5, -0.2, 0.4, 0.6]);
+        // crosses between frames 1 and 2, and between frames 4 and 5
+        assert_eq!(audio.nearest_zero_crossing(ms(0)), ms(2));
+        assert_eq!(audio.nearest_zero_crossing(ms(3)), ms(2));
+        assert_eq!(audio.nearest_zero_crossing(ms(4)), ms(4));
+        assert_eq!(audio.nearest_zero_crossing(ms(6)), ms(4));
+
+        // channels are averaged
+        let audio = Audio::new(2, 1000, vec![0.5, 0.5, 0.5, -0.6, -0.5, -0.5]);
+        assert_eq!(audio.nearest_zero_crossing(ms(0)), ms(1));
+
+        // nothing to snap to
+        let audio = Audio::new(1, 1000, vec![0.5; 4]);
+        assert_eq!(audio.nearest_zero_crossing(ms(15)), ms(15));
+    }
+
+    #[test]
+    fn test_echo() {
+        let mut audio = Audio::new(
+            2,
+            100,
+            vec![1.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        );
+        let mut echo = Effect::Echo {
+            delay: 0.01,
+            feedback: 0.5,
+        };
+        echo.increase(0.01);
+        echo.increase_secondary(-0.25);
+        assert_eq!(echo.params(), ("delay", Some("feedback")));
+        audio.apply(ms(0), ms(50), &echo);
+        assert_eq!(
+            audio.samples,
+            [1.0, -1.0, 0.0, 0.0, 0.25, -0.25, 0.0, 0.0, 0.0625, -0.0625]
+        );
+
+        let mut amplify = Effect::Amplify { amount: 1.0 };
+        amplify.increase_secondary(0.5);
+        assert!(matches!(amplify, Effect::Amplify { amount: 1.0 }));
+    }
+
+    #[test]
+    fn test_gate() {
+        // a loud tone between stretches of quiet hiss
+        let rate = 1000;
+        let hiss = |i: usize| if i.is_multiple_of(2) { 0.005 } else { -0.005 };
+        let samples: Vec<f32> = (0..1000)
+            .map(|i| match i {
+                300..600 => 0.5 * (i as f32 * 0.5).sin(),
+                _ => hiss(i),
+            })
+            .collect();
+        let mut audio = Audio::new(1, rate, samples.clone());
+        let mut gate = Effect::Gate {
+            threshold_db: -30.0,
+            attack: 0.002,
+            release: 0.02,
+        };
+        gate.increase(-0.1);
+        assert!(matches!(
+            gate,
+            Effect::Gate {
+                threshold_db: -31.0,
+                ..
+            }
+        ));
+        assert_eq!(gate.params(), ("threshold", None));
+        audio.apply(ms(0), ms(1000), &gate);
+
+        let peak = |s: &[f32]| s.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+        // the hiss fades out over the release, once the tone has decayed below the threshold
+        assert!(peak(&audio.samples[100..300]) < 1e-4);
+        assert!(peak(&audio.samples[700..800]) < peak(&samples[700..800]));
+        assert!(peak(&audio.samples[800..]) < 1e-4);
+        // the tone is kept once the gate opens
+        for (a, b) in audio.samples[330..600].iter().zip(&samples[330..600]) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+
+        gate.increase(-100.0);
+        assert!(gate.pinned());
+    }
+
+    #[test]
+    fn test_micro_fade() {
+        let mut audio = Audio::new(2, 100, vec![1.0; 20]);
+        audio.micro_fade(ms(10), ms(90), ms(30));
+        assert_eq!(
+            audio.samples,
+            [
+                1.0,
+                1.0, // before the region
+                0.0,
+                0.0,
+                1.0 / 3.0,
+                1.0 / 3.0,
+                2.0 / 3.0,
+                2.0 / 3.0, // fade in
+                1.0,
+                1.0,
+                1.0,
+                1.0, // untouched
+                2.0 / 3.0,
+                2.0 / 3.0,
+                1.0 / 3.0,
+                1.0 / 3.0,
+                0.0,
+                0.0, // fade out
+                1.0,
+                1.0, // after the region
+            ]
+        );
+
+        // no longer than half the region
+        let mut audio = Audio::new(1, 100, vec![1.0; 4]);
+        audio.micro_fade(ms(0), ms(40), ms(1000));
+        assert_eq!(audio.samples, [0.0, 0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_segments() {
+        // 1kHz: loud 0-100ms, a 20ms dip, loud until 200ms, silent until 300ms, loud 300-350ms
+        let mut samples = vec![0.5; 350];
+        samples[100..120].fill(0.0);
+        samples[200..300].fill(0.01);
+        samples[0] = 0.0;
+        let audio = Audio::new(1, 1000, samples);
+        assert_eq!(audio.segments(0.1), [(ms(1), ms(200)), (ms(300), ms(350))]);
+        assert_eq!(audio.segments(0.001), [(ms(1), ms(350))]);
+        assert_eq!(audio.segments(1.0), []);
+    }
+
+    #[test]
+    fn test_effect_limits() {
+        let mut amplify = Effect::Amplify { amount: 1.0 };
+        assert!(!amplify.pinned());
+        for _ in 0..20 {
+            amplify.increase(-0.1);
+        }
+        assert!(matches!(amplify, Effect::Amplify { amount: 0.0 }));
+        assert!(amplify.pinned());
+        amplify.increase(100.0);
+        assert!(matches!(amplify, Effect::Amplify { amount: 16.0 }));
+        assert!(amplify.pinned());
+
+        let mut echo = Effect::Echo {
+            delay: 0.25,
+            feedback: 0.5,
+        };
+        echo.increase(10.0);
+        echo.increase_secondary(10.0);
+        assert!(matches!(
+            echo,
+            Effect::Echo {
+                delay: 2.0,
+                feedback: 0.95
+            }
+        ));
+        assert!(echo.pinned());
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut audio = audio();
+        audio.shift(ms(20), ms(60), 1);
+        assert_eq!(
+            audio.samples,
+            [0.0, 0.1, 0.0, 0.2, 0.3, 0.4, 0.6, 0.7, 0.8, 0.9]
+        );
+        audio.shift(ms(20), ms(60), -2);
+        assert_eq!(
+            audio.samples,
+            [0.0, 0.1, 0.3, 0.4, 0.0, 0.0, 0.6, 0.7, 0.8, 0.9]
+        );
+
+        let mut stereo = Audio::new(2, 100, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        stereo.shift(ms(0), ms(30), 1);
+        assert_eq!(stereo.samples, [0.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+        stereo.shift(ms(0), ms(30), -10);
+        assert_eq!(stereo.samples, [0.0; 6]);
+    }
+}
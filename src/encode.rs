@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use rodio::{buffer::SamplesBuffer, Source};
+use serde::Deserialize;
+
+// Sample format used when writing WAV files, configured as a bit depth.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum WavBits {
+    #[default]
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl TryFrom<u16> for WavBits {
+    type Error = String;
+
+    fn try_from(bits: u16) -> Result<Self, Self::Error> {
+        match bits {
+            16 => Ok(WavBits::Int16),
+            24 => Ok(WavBits::Int24),
+            32 => Ok(WavBits::Float32),
+            bits => Err(format!(
+                "unsupported wav_bits {bits}, expected 16, 24 or 32"
+            )),
+        }
+    }
+}
+
+// Writes the source to path, picking an encoding based on the file extension.
+pub fn write(path: &Path, source: &SamplesBuffer<f32>, bits: WavBits) -> Result<()> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("wav") => write_wav(path, source, bits),
+        Some(ext) => bail!("Cannot encode {path:?}: unsupported format '{ext}'"),
+        None => bail!("Cannot encode {path:?}: no file extension"),
+    }
+}
+
+fn write_wav(path: &Path, source: &SamplesBuffer<f32>, bits: WavBits) -> Result<()> {
+    let (bits_per_sample, sample_format) = match bits {
+        WavBits::Int16 => (16, hound::SampleFormat::Int),
+        WavBits::Int24 => (24, hound::SampleFormat::Int),
+        WavBits::Float32 => (32, hound::SampleFormat::Float),
+    };
+    let spec = hound::WavSpec {
+        channels: source.channels(),
+        sample_rate: source.sample_rate(),
+        bits_per_sample,
+        sample_format,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in source.clone() {
+        match bits {
+            WavBits::Int16 => {
+                writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?
+            }
+            WavBits::Int24 => {
+                const MAX_24: f32 = ((1 << 23) - 1) as f32;
+                writer.write_sample((sample.clamp(-1.0, 1.0) * MAX_24) as i32)?
+            }
+            // float samples may exceed ±1.0, so keep them as-is
+            WavBits::Float32 => writer.write_sample(sample)?,
+        }
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let source = SamplesBuffer::new(2, 8000, vec![0.0, 0.5, -0.5, 1.0, -1.0, 0.25]);
+
+        write(&path, &source, WavBits::default()).unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 8000);
+        assert_eq!(reader.len(), 6);
+    }
+
+    #[test]
+    fn test_write_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.mp3");
+        let source = SamplesBuffer::new(1, 8000, vec![0.0]);
+
+        let err = write(&path, &source, WavBits::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("mp3"), "{err}");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_write_wav_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let source = SamplesBuffer::new(1, 8000, vec![0.0, 0.5, -1.0]);
+
+        write(&path, &source, WavBits::Int16).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Int);
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0, 16383, -32767]);
+
+        write(&path, &source, WavBits::Int24).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Int);
+        let samples: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0, 4194303, -8388607]);
+
+        write(&path, &source, WavBits::Float32).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 32);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+        let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0.0, 0.5, -1.0]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{bail, Context, Result};
+
+// A labelled region in an Audacity-style label track. Points have equal start and end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+// Writes one tab-separated `start end label` line per label, with times in seconds.
+pub fn write(path: &Path, labels: &[Label]) -> Result<()> {
+    let text: String = labels
+        .iter()
+        .map(|l| {
+            format!(
+                "{:.6}\t{:.6}\t{}\n",
+                l.start.as_secs_f64(),
+                l.end.as_secs_f64(),
+                l.text
+            )
+        })
+        .collect();
+    std::fs::write(path, text).with_context(|| format!("Failed to write {path:?}"))
+}
+
+// Parses the lines written by write. Audacity also writes lines starting with `\` for the
+// frequency range of a label, which are skipped.
+pub fn parse(text: &str) -> Result<Vec<Label>> {
+    let mut labels = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let (Some(start), Some(end)) = (fields.next(), fields.next()) else {
+            bail!("Line {}: expected 'start<TAB>end<TAB>label'", i + 1);
+        };
+        let time = |field: &str| -> Result<Duration> {
+            let secs: f64 = field
+                .trim()
+                .parse()
+                .with_context(|| format!("Line {}: invalid time {field:?}", i + 1))?;
+            Duration::try_from_secs_f64(secs)
+                .with_context(|| format!("Line {}: invalid time {field:?}", i + 1))
+        };
+        let (start, end) = (time(start)?, time(end)?);
+        if end < start {
+            bail!("Line {}: ends before it starts", i + 1);
+        }
+        labels.push(Label {
+            start,
+            end,
+            text: fields.next().unwrap_or_default().trim_end().to_string(),
+        });
+    }
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labels.txt");
+        let labels = vec![
+            Label {
+                start: Duration::from_millis(1500),
+                end: Duration::from_millis(1500),
+                text: "verse".into(),
+            },
+            Label {
+                start: Duration::from_millis(2000),
+                end: Duration::from_millis(3250),
+                text: "".into(),
+            },
+        ];
+        write(&path, &labels).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "1.500000\t1.500000\tverse\n2.000000\t3.250000\t\n");
+        assert_eq!(parse(&text).unwrap(), labels);
+    }
+}
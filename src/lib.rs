@@ -1,3 +1,10 @@
 pub mod binds;
 pub mod config;
+pub mod edit;
+pub mod encode;
+pub mod labels;
+pub mod logger;
+pub mod osc52;
+pub mod picker;
+pub mod record;
 pub mod tui;
@@ -0,0 +1,187 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+// Number of records kept for display in the log panel.
+const CAPACITY: usize = 256;
+
+// Recent log records, formatted for display.
+pub static RECORDS: Records = Records::new(CAPACITY);
+
+// The most verbose level captured for the log panel, which can be changed while running.
+pub static LEVEL: Level = Level::new(log::LevelFilter::Debug);
+
+// The level set through RUST_LOG, which is always passed through to env_logger.
+static ENV_LEVEL: Level = Level::new(log::LevelFilter::Off);
+
+// A log level filter that can be shared and changed between threads.
+pub struct Level(AtomicUsize);
+
+impl Level {
+    pub const fn new(level: log::LevelFilter) -> Self {
+        Self(AtomicUsize::new(level as usize))
+    }
+
+    pub fn get(&self) -> log::LevelFilter {
+        log::LevelFilter::iter()
+            .nth(self.0.load(Ordering::Relaxed))
+            .unwrap_or(log::LevelFilter::Trace)
+    }
+
+    pub fn set(&self, level: log::LevelFilter) {
+        self.0.store(level as usize, Ordering::Relaxed);
+    }
+
+    // Steps to the next more verbose level, wrapping from trace back around to error.
+    pub fn cycle(&self) -> log::LevelFilter {
+        let level = match self.get() {
+            log::LevelFilter::Off | log::LevelFilter::Trace => log::LevelFilter::Error,
+            level => log::LevelFilter::iter()
+                .nth(level as usize + 1)
+                .unwrap_or(log::LevelFilter::Error),
+        };
+        self.set(level);
+        level
+    }
+}
+
+// A bounded buffer of log lines that drops the oldest line when full.
+pub struct Records {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl Records {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    // Returns the stored lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+// Captures records into RECORDS while passing them through to env_logger.
+struct Logger {
+    inner: env_logger::Logger,
+    level: &'static Level,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level.get() || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() <= self.level.get() {
+            RECORDS.push(format!("{:<5} {}", record.level(), record.args()));
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+pub fn init() -> Result<(), log::SetLoggerError> {
+    let inner = env_logger::Builder::from_default_env().build();
+    ENV_LEVEL.set(inner.filter());
+    log::set_boxed_logger(Box::new(Logger {
+        inner,
+        level: &LEVEL,
+    }))?;
+    update_max_level();
+    Ok(())
+}
+
+// Cycles the level captured for the log panel, returning the new level.
+pub fn cycle_level() -> log::LevelFilter {
+    let level = LEVEL.cycle();
+    update_max_level();
+    level
+}
+
+// Lets through records needed by either the log panel or env_logger.
+fn update_max_level() {
+    log::set_max_level(LEVEL.get().max(ENV_LEVEL.get()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_records_capacity() {
+        let records = Records::new(2);
+        records.push("a".into());
+        records.push("b".into());
+        records.push("c".into());
+        assert_eq!(records.lines(), ["b", "c"]);
+    }
+
+    #[test]
+    fn test_logger() {
+        static LEVEL: Level = Level::new(log::LevelFilter::Debug);
+        let logger = Logger {
+            inner: env_logger::Builder::new().build(),
+            level: &LEVEL,
+        };
+        logger.log(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .args(format_args!("hello {}", "log"))
+                .build(),
+        );
+        logger.log(
+            &log::Record::builder()
+                .level(log::Level::Trace)
+                .args(format_args!("too verbose"))
+                .build(),
+        );
+        let lines = RECORDS.lines();
+        assert!(lines.contains(&"INFO  hello log".to_string()));
+        assert!(!lines.iter().any(|l| l.contains("too verbose")));
+    }
+
+    #[test]
+    fn test_level_cycle() {
+        let level = Level::new(log::LevelFilter::Debug);
+        assert_eq!(level.cycle(), log::LevelFilter::Trace);
+        assert_eq!(level.cycle(), log::LevelFilter::Error);
+        assert_eq!(level.cycle(), log::LevelFilter::Warn);
+        assert_eq!(level.cycle(), log::LevelFilter::Info);
+        assert_eq!(level.cycle(), log::LevelFilter::Debug);
+        assert_eq!(level.get(), log::LevelFilter::Debug);
+
+        static LEVEL: Level = Level::new(log::LevelFilter::Warn);
+        let logger = Logger {
+            inner: env_logger::Builder::new().build(),
+            level: &LEVEL,
+        };
+        let info = log::Metadata::builder().level(log::Level::Info).build();
+        assert!(!logger.enabled(&info));
+        LEVEL.cycle();
+        assert!(logger.enabled(&info));
+    }
+}
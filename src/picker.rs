@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+// A directory listing for choosing a file, narrowed by typing part of a name.
+pub struct Picker {
+    pub dir: PathBuf,
+    // Entry names, with directories first and ending in '/'.
+    entries: Vec<String>,
+    pub filter: String,
+    // Index into the visible entries.
+    pub selected: usize,
+}
+
+impl Picker {
+    pub fn open(dir: &Path) -> Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to open {dir:?}"))?;
+        let mut entries: Vec<(bool, String)> = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to list {dir:?}"))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.path().is_dir() {
+                    (false, format!("{name}/"))
+                } else {
+                    (true, name)
+                }
+            })
+            .collect();
+        entries.sort_by_key(|(is_file, name)| (*is_file, name.to_lowercase()));
+        let parent = dir.parent().map(|_| "../".to_string());
+        Ok(Self {
+            dir,
+            entries: parent
+                .into_iter()
+                .chain(entries.into_iter().map(|(_, name)| name))
+                .collect(),
+            filter: String::new(),
+            selected: 0,
+        })
+    }
+
+    // The entries containing the filter, ignoring case.
+    pub fn visible(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|name| name.to_lowercase().contains(&filter))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.filter.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.filter.pop();
+        self.selected = 0;
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = (self.selected + 1).min(last);
+    }
+
+    // Lists the selected directory in place, or returns the path of the selected file.
+    pub fn enter(&mut self) -> Result<Option<PathBuf>> {
+        let Some(name) = self.visible().get(self.selected).map(|s| s.to_string()) else {
+            return Ok(None);
+        };
+        let path = match name.strip_suffix('/') {
+            Some("..") => self.dir.parent().unwrap_or(&self.dir).to_path_buf(),
+            Some(dir) => self.dir.join(dir),
+            None => return Ok(Some(self.dir.join(name))),
+        };
+        *self = Picker::open(&path)?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["kick.wav", "Snare.wav", "notes.txt", "drums/hat.wav"] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_filter() {
+        let dir = dir();
+        let mut picker = Picker::open(dir.path()).unwrap();
+        assert_eq!(
+            picker.visible(),
+            ["../", "drums/", "kick.wav", "notes.txt", "Snare.wav"]
+        );
+
+        picker.down();
+        picker.down();
+        assert_eq!(picker.selected, 2);
+        for c in "WAV".chars() {
+            picker.push(c);
+        }
+        assert_eq!(picker.visible(), ["kick.wav", "Snare.wav"]);
+        assert_eq!(picker.selected, 0);
+        picker.down();
+        picker.down();
+        assert_eq!(picker.selected, 1);
+        assert_eq!(
+            picker.enter().unwrap(),
+            Some(dir.path().canonicalize().unwrap().join("Snare.wav"))
+        );
+
+        picker.push('x');
+        assert!(picker.visible().is_empty());
+        assert_eq!(picker.enter().unwrap(), None);
+        picker.pop();
+        picker.up();
+        assert_eq!(picker.visible()[picker.selected], "kick.wav");
+    }
+
+    #[test]
+    fn test_traverse() {
+        let dir = dir();
+        let root = dir.path().canonicalize().unwrap();
+        let mut picker = Picker::open(dir.path()).unwrap();
+
+        picker.push('d');
+        assert_eq!(picker.visible(), ["drums/"]);
+        assert_eq!(picker.enter().unwrap(), None);
+        assert_eq!(picker.dir, root.join("drums"));
+        assert_eq!(picker.filter, "");
+        assert_eq!(picker.visible(), ["../", "hat.wav"]);
+
+        assert_eq!(picker.enter().unwrap(), None);
+        assert_eq!(picker.dir, root);
+        assert_eq!(picker.visible().len(), 5);
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use rodio::cpal::{
+    self,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    FromSample, Sample, SizedSample,
+};
+
+use crate::edit::Audio;
+
+// Captures audio from the default input device until stopped.
+pub struct Recorder {
+    _stream: cpal::Stream,
+    channels: u16,
+    sample_rate: u32,
+    samples: Arc<Mutex<Vec<f32>>>,
+}
+
+impl Recorder {
+    pub fn start() -> Result<Self> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .context("No input device available")?;
+        let supported = device.default_input_config()?;
+        let config = supported.config();
+        log::info!(
+            "Recording from {:?} ({config:?}, {:?})",
+            device.name(),
+            supported.sample_format()
+        );
+
+        let samples = Arc::new(Mutex::new(vec![]));
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, samples.clone())?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, samples.clone())?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, samples.clone())?,
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, samples.clone())?,
+            format => bail!("Unsupported input sample format {format:?}"),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            channels: config.channels,
+            sample_rate: config.sample_rate.0,
+            samples,
+        })
+    }
+
+    // Stops recording and returns everything captured so far.
+    pub fn stop(self) -> Audio {
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        Audio::new(self.channels, self.sample_rate, samples)
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    Ok(device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| append(&mut samples.lock().unwrap(), data),
+        |err| log::error!("Recording error: {err}"),
+        None,
+    )?)
+}
+
+fn append<T>(samples: &mut Vec<f32>, data: &[T])
+where
+    T: Sample,
+    f32: FromSample<T>,
+{
+    samples.extend(data.iter().map(|v| v.to_sample::<f32>()));
+}
+
+// Converts recorded audio to the given format so it can be inserted into existing audio.
+pub fn conform(audio: Audio, channels: u16, sample_rate: u32) -> Audio {
+    let audio = audio.resample(sample_rate);
+    match (audio.channels, channels) {
+        (from, to) if from == to => audio,
+        (_, 1) => audio.downmix(),
+        (1, 2) => audio.upmix(),
+        (_, 2) => audio.downmix().upmix(),
+        (from, to) => {
+            log::warn!("Cannot convert {from} channels to {to}, recording in mono");
+            let mono = audio.downmix();
+            let samples = mono
+                .samples
+                .iter()
+                .flat_map(|&v| std::iter::repeat_n(v, to as usize))
+                .collect();
+            Audio::new(to, sample_rate, samples)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_append() {
+        let mut samples = vec![];
+        append(&mut samples, &[0i16, i16::MIN]);
+        append(&mut samples, &[16384i16]);
+        assert_eq!(samples, [0.0, -1.0, 0.5]);
+
+        let mut samples = vec![];
+        append(&mut samples, &[32768u16, 0u16]);
+        assert_eq!(samples, [0.0, -1.0]);
+    }
+
+    #[test]
+    fn test_conform() {
+        let stereo = Audio::new(2, 100, vec![1.0, 0.0, 0.5, 0.5]);
+        assert_eq!(
+            conform(stereo.clone(), 1, 100),
+            Audio::new(1, 100, vec![0.5, 0.5])
+        );
+        assert_eq!(
+            conform(stereo.clone(), 2, 200).samples,
+            [1.0, 0.0, 0.75, 0.25, 0.5, 0.5, 0.5, 0.5]
+        );
+
+        let mono = Audio::new(1, 100, vec![0.5, -0.5]);
+        assert_eq!(
+            conform(mono, 2, 100),
+            Audio::new(2, 100, vec![0.5, 0.5, -0.5, -0.5])
+        );
+
+        let quad = Audio::new(4, 100, vec![1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(conform(quad, 2, 100), Audio::new(2, 100, vec![0.5, 0.5]));
+    }
+}
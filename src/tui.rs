@@ -1,20 +1,48 @@
-use std::{fs::File, io::BufReader, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::File,
+    io::BufReader,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
-use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+};
 
 use ratatui::{
     prelude::*,
-    widgets::{block::Title, Axis, Block, Chart, Dataset, GraphType},
+    widgets::{block::Title, Axis, Block, Chart, Dataset, GraphType, List, Sparkline},
+};
+use rodio::{
+    buffer::SamplesBuffer, decoder::DecoderError, source::UniformSourceIterator, Decoder,
+    OutputStream, Sink, Source,
 };
-use rodio::{buffer::SamplesBuffer, Decoder, OutputStream, Sink, Source};
 
 use crate::{
-    binds::Binds,
-    config::{Action, Config},
+    binds::{Binding, Binds},
+    config::{Action, Config, PlayAtEnd},
+    edit::{self, Audio, Effect},
+    encode::{self, WavBits},
+    labels, logger, osc52,
+    picker::Picker,
+    record::{self, Recorder},
 };
 
+// The narrowest the view can be zoomed to.
+const MIN_WINDOW: Duration = Duration::from_millis(1);
+
+// Length of audio played on each cursor move while scrubbing.
+const SCRUB_GRAIN: Duration = Duration::from_millis(50);
+
+// How much log-time view magnifies the area around the cursor.
+const LOG_TIME_ZOOM: f64 = 100.0;
+
+// Colors of the playhead trail, from the most recent position back.
+const TRAIL_COLORS: [Color; 3] = [Color::Indexed(160), Color::Indexed(124), Color::Indexed(88)];
+
 #[derive(Clone)]
 struct Selection {
     start: Duration,
@@ -32,63 +60,428 @@ impl Selection {
     }
 }
 
-enum Effect {
-    Amplify { amount: f32 },
+#[derive(Clone)]
+struct Marker {
+    pos: Duration,
+    label: Option<String>,
+    category: Category,
+}
+
+// Used to sort markers while reviewing, shown by the marker's color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Category {
+    Plain,
+    Good,
+    Bad,
+    Todo,
+}
+
+impl Category {
+    fn next(self) -> Self {
+        match self {
+            Category::Plain => Category::Good,
+            Category::Good => Category::Bad,
+            Category::Bad => Category::Todo,
+            Category::Todo => Category::Plain,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Category::Plain => "plain",
+            Category::Good => "good",
+            Category::Bad => "bad",
+            Category::Todo => "todo",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Category::Plain => Color::Yellow,
+            Category::Good => Color::LightGreen,
+            Category::Bad => Color::LightRed,
+            Category::Todo => Color::Magenta,
+        }
+    }
+}
+
+enum Prompt {
+    InsertTone,
+    GoToSample,
+    SaveAs,
+    Reference,
+    ImportLabels,
+    MarkerLabel { pos: Duration },
+    // Jumps to matching markers while typing, returning to origin if cancelled.
+    SearchMarker { origin: Duration },
 }
 
-impl Effect {
-    fn increase(&mut self, delta: f32) {
+impl Prompt {
+    fn label(&self) -> &'static str {
         match self {
-            Effect::Amplify { amount } => *amount += delta,
+            Prompt::InsertTone => "tone (hz secs): ",
+            Prompt::GoToSample => "go to sample: ",
+            Prompt::SaveAs => "save as: ",
+            Prompt::Reference => "reference (empty to clear): ",
+            Prompt::ImportLabels => "import labels: ",
+            Prompt::MarkerLabel { .. } => "marker label: ",
+            Prompt::SearchMarker { .. } => "search markers: ",
         }
     }
+}
+
+enum Confirm {
+    Overwrite(std::path::PathBuf),
+    Revert,
+    // Opening another file with unsaved changes.
+    Open(std::path::PathBuf),
+    Quit,
+    Normalize,
+    Downmix,
+    ExtractAround,
+    MatchLoudness,
+    ApplyEffect {
+        selection: Selection,
+        effect: Effect,
+        selected: bool,
+    },
+}
 
-    fn apply(&self, src: impl Source<Item = f32>) -> impl Source<Item = f32> {
+impl Confirm {
+    fn label(&self) -> String {
         match self {
-            Effect::Amplify { amount } => src.amplify(*amount),
+            Confirm::Overwrite(path) => format!("overwrite {}? (y/n)", path.display()),
+            Confirm::Revert => "discard all changes? (y/n)".into(),
+            Confirm::Open(path) => format!("discard changes and open {}? (y/n)", path.display()),
+            Confirm::Quit => "save changes before quitting? (y)es/(n)o/(c)ancel".into(),
+            Confirm::Normalize => "normalize the whole file? (y/n)".into(),
+            Confirm::Downmix => "mix the whole file down to mono? (y/n)".into(),
+            Confirm::ExtractAround => "discard everything away from the cursor? (y/n)".into(),
+            Confirm::MatchLoudness => "match the whole file to the reference? (y/n)".into(),
+            Confirm::ApplyEffect { effect, .. } => {
+                format!("apply {} to the whole file? (y/n)", effect.describe())
+            }
         }
     }
 }
 
+// Work that blocks the UI, run once a frame showing that it's underway has been drawn.
+struct Busy {
+    label: &'static str,
+    job: Box<dyn FnOnce(&mut App)>,
+}
+
+// A (seconds, amplitude) point on the chart.
+type Point = (f64, f64);
+
+// Waveform points from the last render, along with the window and width they were computed for.
+struct WaveCache {
+    window: (Duration, Duration),
+    width: u16,
+    points: Vec<(f64, f64)>,
+}
+
+// Total gain from repeatedly amplifying the same selection above which a warning is logged.
+const STACKED_GAIN_WARNING_DB: f32 = 12.0;
+
+// How far NudgeContentLeft/NudgeContentRight move the audio in the selection.
+const NUDGE: Duration = Duration::from_millis(1);
+
+// Rows taken by the overview of the whole file shown when zoomed in.
+const MINIMAP_HEIGHT: u16 = 6;
+
+// TapTempo starts over if there is a longer gap than this between taps.
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+// Number of most recent taps TapTempo averages over.
+const TAP_COUNT: usize = 8;
+
+// How far PlayClock may run ahead of the last reported position, so a stalled output
+// doesn't leave the playhead running on.
+const MAX_PLAYHEAD_DRIFT: Duration = Duration::from_millis(250);
+
+// Interpolates the playback position, which the sink may only update every so often, by
+// advancing it with wall-clock time and resyncing whenever the sink reports a new position.
+struct PlayClock {
+    reported: Duration,
+    reported_at: Instant,
+}
+
+impl PlayClock {
+    fn new(reported: Duration, now: Instant) -> Self {
+        Self {
+            reported,
+            reported_at: now,
+        }
+    }
+
+    fn position(&mut self, reported: Duration, now: Instant) -> Duration {
+        if reported != self.reported {
+            self.reported = reported;
+            self.reported_at = now;
+        }
+        let elapsed = now.saturating_duration_since(self.reported_at);
+        self.reported + elapsed.min(MAX_PLAYHEAD_DRIFT)
+    }
+}
+
+enum Direction {
+    Undo,
+    Redo,
+}
+
 enum Mode {
     Normal,
     Select(Selection),
     Effect {
         selection: Selection,
         effect: Effect,
+        // Whether the effect was started from a selection, which cancelling returns to.
+        selected: bool,
+    },
+    Prompt {
+        prompt: Prompt,
+        input: String,
     },
+    Confirm(Confirm),
+    // Choosing a file to open.
+    Pick(Picker),
+}
+
+impl From<&SamplesBuffer<f32>> for Audio {
+    fn from(source: &SamplesBuffer<f32>) -> Self {
+        Audio::new(
+            source.channels(),
+            source.sample_rate(),
+            source.clone().collect(),
+        )
+    }
+}
+
+impl From<Audio> for SamplesBuffer<f32> {
+    fn from(audio: Audio) -> Self {
+        SamplesBuffer::new(audio.channels, audio.sample_rate, audio.samples)
+    }
+}
+
+// Formats rodio is built to decode.
+const SUPPORTED_FORMATS: &str = "wav, flac, ogg vorbis, mp3";
+
+// Opens and decodes the file at path, explaining what went wrong if it can't.
+fn decode(path: &std::path::Path) -> Result<Decoder<BufReader<File>>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!("{} not found", path.display())
+        }
+        Err(err) => bail!("Cannot open {}: {err}", path.display()),
+    };
+    Decoder::new(BufReader::new(file)).map_err(|err| match err {
+        DecoderError::UnrecognizedFormat => anyhow!(
+            "{} is not in a supported audio format ({SUPPORTED_FORMATS})",
+            path.display()
+        ),
+        DecoderError::DecodeError(msg) => {
+            anyhow!("{} appears to be corrupt: {msg}", path.display())
+        }
+        err => anyhow!("Cannot decode {}: {err}", path.display()),
+    })
+}
+
+// Decodes the whole file at path into memory.
+fn load(path: &std::path::Path) -> Result<SamplesBuffer<f32>> {
+    let decoder = decode(path)?;
+    Ok(SamplesBuffer::new(
+        decoder.channels(),
+        decoder.sample_rate(),
+        decoder.convert_samples().collect::<Vec<_>>(),
+    ))
+}
+
+// Returns the sample rate OutputStream::try_default opens the default output device with.
+fn output_rate() -> Option<u32> {
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+    let device = rodio::cpal::default_host().default_output_device()?;
+    match device.default_output_config() {
+        Ok(config) => Some(config.sample_rate().0),
+        Err(err) => {
+            log::warn!("Cannot get the output sample rate: {err}");
+            None
+        }
+    }
+}
+
+// Returns count labels for amplitudes evenly spaced between min and max, each with enough
+// precision to tell it apart from its neighbors.
+fn amplitude_labels(min: f64, max: f64, count: usize) -> Vec<String> {
+    let step = (max - min) / (count - 1) as f64;
+    let precision = (1..3)
+        .find(|&p| {
+            let scaled = step * 10f64.powi(p as i32);
+            (scaled - scaled.round()).abs() < 1e-9
+        })
+        .unwrap_or(3);
+    (0..count)
+        .map(|i| format!("{:.precision$}", min + step * i as f64))
+        .collect()
+}
+
+// Shows the whole file at first, or a second if it's empty.
+fn initial_window_end(duration: Duration) -> Duration {
+    if duration.is_zero() {
+        Duration::from_secs(1)
+    } else {
+        duration
+    }
+}
+
+// Formats pos as mm:ss.mmm, with minutes going past 59 rather than adding hours.
+fn timecode(pos: Duration) -> String {
+    let millis = pos.as_millis();
+    format!(
+        "{:02}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
 }
 
 struct App {
     exit: bool,
+    // The source has changes that haven't been saved.
+    dirty: bool,
     binds: Binds<Action>,
     path: std::path::PathBuf,
     _stream: OutputStream,
     sink: Sink,
+    // The sample rate of the output, if known. Played audio is resampled to match it.
+    output_rate: Option<u32>,
     source: SamplesBuffer<f32>,
+    // Read-only audio drawn behind the source for alignment.
+    reference: Option<SamplesBuffer<f32>>,
     cursor: Duration,
+    // Where the cursor was before the last jump, for JumpBack.
+    jumped_from: Option<Duration>,
+    // Where the last jump went from and to, for NavBack.
+    last_jump: Option<(Duration, Duration)>,
     playhead: Duration,
+    // Set while playing if smooth_playhead is enabled.
+    play_clock: Option<PlayClock>,
+    smooth_playhead: bool,
     window_start: Duration,
     window_end: Duration,
     playing: bool,
     mode: Mode,
+    busy: Option<Busy>,
+    // Sorted by position.
+    markers: Vec<Marker>,
+    effect_step: f32,
+    fine_effect_step: f32,
+    clipboard: Option<Audio>,
+    // Where clipboard escape sequences for text are written, normally the terminal.
+    text_clipboard: Box<dyn std::io::Write>,
+    bpm: Option<f64>,
+    // Times of the recent TapTempo presses, oldest first.
+    taps: Vec<Instant>,
+    snap: bool,
+    snap_cuts_to_zero: bool,
+    fps: f64,
+    // Message shown at the bottom until the next key press.
+    status: Option<String>,
+    // Downmix playback to mono without changing the source.
+    mono_monitor: bool,
+    // Mix a click into playback at each beat.
+    click: bool,
+    show_log: bool,
+    show_instructions: bool,
+    // Draw the selection without the effect behind the preview.
+    effect_compare: bool,
+    // Show the sample values under the cursor.
+    sample_readout: bool,
+    // Play a short grain at the cursor each time it moves.
+    scrub: bool,
+    normalize_dbfs: f32,
+    silence_threshold_db: f32,
+    // Effects applied to the whole file when saving or exporting, in order.
+    effect_chain: Vec<Effect>,
+    // Number of edits made this session.
+    edits: usize,
+    // Region the last edit amplified and the total gain consecutively applied to it.
+    stacked_gain: Option<((Duration, Duration), f32)>,
+    // Previous versions of the source, most recent last.
+    undo: Vec<SamplesBuffer<f32>>,
+    undo_limit: usize,
+    redo: Vec<SamplesBuffer<f32>>,
+    limit_playback: bool,
+    loop_crossfade: Duration,
+    anti_click: Duration,
+    micro_fade: Duration,
+    play_at_end: PlayAtEnd,
+    play_at_end_length: Duration,
+    extract_radius: Duration,
+    conform_paste: bool,
+    confirm_destructive: bool,
+    fast_zoom: f64,
+    y_ticks: usize,
+    sample_axis: bool,
+    // Experimental view that spaces time logarithmically away from the cursor.
+    log_time: bool,
+    wav_bits: WavBits,
+    // Start and length of the region being played on repeat.
+    looping: Option<(Duration, Duration)>,
+    // Start over from the beginning of the file when playback reaches the end.
+    loop_all: bool,
+    // Start and end of the region PlayLoop repeats when nothing is selected.
+    loop_region: Option<(Duration, Duration)>,
+    // Number of lines the log panel is scrolled back from the newest.
+    log_scroll: usize,
+    wave_cache: RefCell<Option<WaveCache>>,
+    // Minimap peaks from the last render, along with the width they were computed for.
+    overview_cache: RefCell<Option<(u16, Vec<u64>)>>,
+    recorder: Option<Recorder>,
+    // Actions from macros waiting to run, and when, soonest first.
+    scheduled: Vec<(Instant, Action)>,
+    // Set when something visible changed and cleared by drawing.
+    needs_redraw: bool,
+    last_draw: Option<Instant>,
+    // Minimum time between draws, from max_fps.
+    frame_interval: Duration,
+    // How often to wake up to move the playhead.
+    poll_interval: Duration,
+    center_cursor: bool,
+    // Recent playhead positions, newest first, if playhead_trail is set.
+    trail: Option<VecDeque<Duration>>,
 }
 
 impl App {
     fn new(config: Config, path: std::path::PathBuf) -> Result<Self> {
+        let source = decode(&path)?;
+        Self::with_source(config, path, source)
+    }
+
+    fn with_source<S>(config: Config, path: std::path::PathBuf, source: S) -> Result<Self>
+    where
+        S: Source,
+        S::Item: rodio::Sample,
+        f32: rodio::cpal::FromSample<S::Item>,
+    {
         let binds = Binds::new(config.binds);
         log::trace!("Using binds: {binds:#?}");
         let (stream, stream_handle) = OutputStream::try_default()?;
-
-        let file = BufReader::new(File::open(&path)?);
-        let source = Decoder::new(file)?;
         let sink = Sink::try_new(&stream_handle)?;
-        let window_end = source.total_duration().unwrap_or(Duration::from_secs(1));
+        let output_rate = output_rate();
+        log::debug!("Output sample rate: {output_rate:?}");
+
         let source = SamplesBuffer::new(
             source.channels(),
             source.sample_rate(),
             source.convert_samples().collect::<Vec<_>>(),
         );
+        // Some formats don't report a duration up front, so measure what was decoded.
+        let duration = Audio::from(&source).duration();
+        let window_end = initial_window_end(duration);
+        let status = duration
+            .is_zero()
+            .then(|| format!("{} contains no audio", path.display()));
 
         Ok(Self {
             path,
@@ -96,35 +489,156 @@ impl App {
             _stream: stream,
             source,
             sink,
+            output_rate,
+            reference: None,
             cursor: Duration::ZERO,
+            jumped_from: None,
+            last_jump: None,
             playhead: Duration::ZERO,
+            play_clock: None,
+            smooth_playhead: config.smooth_playhead,
             window_start: Duration::ZERO,
             window_end,
             exit: false,
+            dirty: false,
             playing: false,
             mode: Mode::Normal,
+            busy: None,
+            markers: vec![],
+            effect_step: config.effect_step,
+            fine_effect_step: config.fine_effect_step,
+            clipboard: None,
+            text_clipboard: Box::new(std::io::stdout()),
+            bpm: config.bpm,
+            taps: vec![],
+            snap: config.snap,
+            snap_cuts_to_zero: config.snap_cuts_to_zero,
+            fps: config.fps,
+            status,
+            mono_monitor: false,
+            click: false,
+            show_log: false,
+            show_instructions: true,
+            effect_compare: false,
+            sample_readout: false,
+            scrub: false,
+            normalize_dbfs: config.normalize_dbfs,
+            silence_threshold_db: config.silence_threshold_db,
+            effect_chain: vec![],
+            edits: 0,
+            stacked_gain: None,
+            undo: vec![],
+            undo_limit: config.undo_limit,
+            redo: vec![],
+            limit_playback: config.limit_playback,
+            loop_crossfade: Duration::from_millis(config.loop_crossfade),
+            anti_click: Duration::from_millis(config.anti_click),
+            micro_fade: Duration::from_millis(config.micro_fade),
+            play_at_end: config.play_at_end,
+            play_at_end_length: Duration::from_millis(config.play_at_end_length),
+            extract_radius: Duration::from_millis(config.extract_radius),
+            conform_paste: config.conform_paste,
+            confirm_destructive: config.confirm_destructive,
+            // zooming by less than 1x would go the wrong way
+            fast_zoom: config.fast_zoom.max(1.0),
+            // at least label both ends of the axis
+            y_ticks: config.y_ticks.max(2),
+            wav_bits: config.wav_bits,
+            sample_axis: config.sample_axis,
+            log_time: false,
+            looping: None,
+            loop_all: false,
+            loop_region: None,
+            log_scroll: 0,
+            wave_cache: RefCell::new(None),
+            overview_cache: RefCell::new(None),
+            recorder: None,
+            scheduled: vec![],
+            needs_redraw: true,
+            last_draw: None,
+            poll_interval: Duration::from_millis(config.poll_interval_ms.max(1)),
+            center_cursor: config.center_cursor,
+            trail: config
+                .playhead_trail
+                .then(|| VecDeque::with_capacity(TRAIL_COLORS.len() + 1)),
+            frame_interval: if config.max_fps > 0.0 {
+                Duration::from_secs_f64(1.0 / config.max_fps)
+            } else {
+                Duration::ZERO
+            },
         })
     }
 
     fn run(&mut self, mut terminal: ratatui::DefaultTerminal) -> Result<()> {
         while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            self.handle_events()?;
+            let now = Instant::now();
+            // nothing is drawn while busy, so draw the busy status first regardless of fps
+            if self.frame_due(now) || self.busy.is_some() {
+                terminal.draw(|frame| self.draw(frame))?;
+                self.drawn(now);
+            }
+            if self.busy.is_some() {
+                self.run_busy();
+            } else {
+                self.handle_events()?;
+            }
         }
         Ok(())
     }
 
+    // Queues a slow job to run after the next draw, which shows label in the status line.
+    fn busy(&mut self, label: &'static str, job: impl FnOnce(&mut App) + 'static) {
+        log::debug!("{label}");
+        self.status = Some(format!("{label}…"));
+        self.busy = Some(Busy {
+            label,
+            job: Box::new(job),
+        });
+        self.needs_redraw = true;
+    }
+
+    fn run_busy(&mut self) {
+        let Some(Busy { label, job }) = self.busy.take() else {
+            return;
+        };
+        let start = Instant::now();
+        self.status = None;
+        job(self);
+        log::debug!("Finished {label} in {:?}", start.elapsed());
+        self.needs_redraw = true;
+    }
+
+    // Whether anything changed since the last draw, and enough time has passed to draw again.
+    fn frame_due(&self, now: Instant) -> bool {
+        self.needs_redraw
+            && self
+                .last_draw
+                .is_none_or(|last| now.duration_since(last) >= self.frame_interval)
+    }
+
+    fn drawn(&mut self, at: Instant) {
+        self.needs_redraw = false;
+        self.last_draw = Some(at);
+    }
+
     fn draw(&self, frame: &mut Frame) {
         frame.render_widget(self, frame.area());
     }
 
+    // Returns the length of the audio, measured from the samples if the source doesn't say.
+    fn duration(&self) -> Duration {
+        self.source
+            .total_duration()
+            .unwrap_or_else(|| Audio::from(&self.source).duration())
+    }
+
     fn move_cursor_to(&mut self, pos: Duration) {
-        self.cursor = pos.clamp(
-            Duration::ZERO,
-            self.source.total_duration().unwrap_or(Duration::MAX),
-        );
+        self.cursor = pos.clamp(Duration::ZERO, self.duration());
         log::debug!("Moved cursor to: {:?}", self.cursor);
 
+        if self.center_cursor {
+            self.center_on(self.cursor);
+        }
         if self.cursor < self.window_start {
             let diff = self.window_start - self.cursor;
             self.window_start -= diff;
@@ -141,189 +655,2147 @@ impl App {
             self.window_end
         );
 
-        match &mut self.mode {
-            Mode::Select(sel) => sel.start = self.cursor,
-            _ => {}
+        if let Mode::Select(sel) = &mut self.mode {
+            sel.start = self.cursor;
         }
     }
 
-    fn apply_action(&mut self, action: Action) -> Result<()> {
-        log::trace!("Applying action: {action:?}");
-        match action {
-            Action::Quit => {
-                log::info!("Exit requested");
-                self.exit = true;
-            }
-            Action::Save => {
-                log::info!("TODO Save not handled");
-            }
-            Action::CursorLeft => {
-                self.move_cursor_to(self.cursor.saturating_sub(Duration::from_millis(10)));
-            }
-            Action::CursorRight => {
-                self.move_cursor_to(self.cursor.saturating_add(Duration::from_millis(10)));
+    // Moves the cursor somewhere non-adjacent, remembering where it was for JumpBack.
+    fn jump_cursor_to(&mut self, pos: Duration) {
+        let from = self.cursor;
+        self.jumped_from = Some(from);
+        self.move_cursor_to(pos);
+        self.last_jump = Some((from, self.cursor));
+    }
+
+    // Inserts recorded audio at the cursor, converting it to match the existing audio.
+    // If there is no existing audio, the recording's format is used as-is.
+    fn insert_recording(&mut self, recording: Audio) {
+        let duration = recording.duration();
+        self.status = Some(format!("Recorded {:.2}s", duration.as_secs_f64()));
+        if self.duration().is_zero() {
+            self.set_source(recording.into());
+        } else {
+            let recording =
+                record::conform(recording, self.source.channels(), self.source.sample_rate());
+            self.splice(self.cursor, self.cursor, recording.samples);
+        }
+        self.move_cursor_to(self.cursor + duration);
+    }
+
+    // Moves the window so pos is in the middle, without going past either end of the audio.
+    fn center_on(&mut self, pos: Duration) {
+        let width = self.window_end - self.window_start;
+        let latest = self.duration().saturating_sub(width);
+        self.window_start = pos.saturating_sub(width / 2).min(latest);
+        self.window_end = self.window_start + width;
+        log::debug!(
+            "Centered window at ({:?}, {:?})",
+            self.window_start,
+            self.window_end
+        );
+    }
+
+    // Keeps the window at least MIN_WINDOW wide and within the file, moving the start back
+    // if the window would run past the end.
+    fn clamp_window(&mut self) {
+        let limit = initial_window_end(self.duration());
+        let width = self
+            .window_end
+            .saturating_sub(self.window_start)
+            .max(MIN_WINDOW)
+            .min(limit);
+        self.window_start = self.window_start.min(limit - width);
+        self.window_end = self.window_start + width;
+        log::debug!("Window is ({:?}, {:?})", self.window_start, self.window_end);
+    }
+
+    // Returns the clipboard in the format of the source, or None if it is empty or cannot be
+    // pasted. Mismatched audio would otherwise play at the wrong pitch or speed.
+    fn clip(&mut self) -> Option<Audio> {
+        let Some(clip) = &self.clipboard else {
+            log::debug!("Nothing to paste");
+            return None;
+        };
+        let (channels, sample_rate) = (self.source.channels(), self.source.sample_rate());
+        if (clip.channels, clip.sample_rate) == (channels, sample_rate) {
+            return Some(clip.clone());
+        }
+        let mismatch = format!(
+            "Clipboard is {}Hz {}ch but the file is {sample_rate}Hz {channels}ch",
+            clip.sample_rate, clip.channels
+        );
+        if !self.conform_paste {
+            log::warn!("{mismatch}");
+            self.status = Some(format!("Cannot paste: {mismatch}"));
+            return None;
+        }
+        log::info!("{mismatch}, converting");
+        Some(record::conform(clip.clone(), channels, sample_rate))
+    }
+
+    // Replaces the audio between start and end with the given samples.
+    fn splice(&mut self, start: Duration, end: Duration, samples: Vec<f32>) {
+        let mut audio = Audio::from(&self.source);
+        audio.splice(start, end, samples);
+        self.set_source(audio.into());
+    }
+
+    // Replaces the audio, keeping the previous version so it can be undone.
+    fn set_source(&mut self, source: SamplesBuffer<f32>) {
+        let prev = std::mem::replace(&mut self.source, source);
+        self.undo.push(prev);
+        if self.undo.len() > self.undo_limit {
+            let excess = self.undo.len() - self.undo_limit;
+            self.undo.drain(..excess);
+        }
+        self.redo.clear();
+        self.wave_cache.take();
+        self.overview_cache.take();
+        self.dirty = true;
+        self.edits += 1;
+        self.stacked_gain = None;
+    }
+
+    // Swaps the current audio for the latest version in the undo or redo history.
+    fn restore(&mut self, direction: Direction) {
+        let (from, to, name) = match direction {
+            Direction::Undo => (&mut self.undo, &mut self.redo, "undo"),
+            Direction::Redo => (&mut self.redo, &mut self.undo, "redo"),
+        };
+        let Some(source) = from.pop() else {
+            self.status = Some(format!("Nothing to {name}"));
+            return;
+        };
+        log::debug!("Applying {name}");
+        to.push(std::mem::replace(&mut self.source, source));
+        self.wave_cache.take();
+        self.overview_cache.take();
+        self.dirty = true;
+        self.move_cursor_to(self.cursor);
+    }
+
+    // Applies the effect chain to audio that is about to be written.
+    fn output(&self, mut audio: Audio) -> SamplesBuffer<f32> {
+        for effect in &self.effect_chain {
+            effect.apply(&mut audio.samples, audio.channels, audio.sample_rate);
+        }
+        audio.into()
+    }
+
+    fn load_reference(&mut self, path: &std::path::Path) {
+        match load(path) {
+            Ok(reference) => {
+                log::info!("Loaded reference {path:?}");
+                self.reference = Some(reference);
             }
-            Action::CursorStart => {
-                self.move_cursor_to(Duration::ZERO);
+            Err(err) => {
+                log::error!("Failed to load reference: {err:?}");
+                self.status = Some(format!("Cannot load reference: {err}"));
             }
-            Action::CursorEnd => {
-                if let Some(end) = self.source.total_duration() {
-                    self.move_cursor_to(end);
-                }
+        }
+    }
+
+    // Terminals paste the path of a file dropped onto them, so outside of a prompt a pasted
+    // path is loaded as the reference track.
+    fn handle_paste(&mut self, text: String) {
+        if let Mode::Prompt { input, .. } = &mut self.mode {
+            input.push_str(&text);
+            return;
+        }
+        let path = dropped_path(&text);
+        if path.is_file() {
+            self.load_reference(&path);
+        } else {
+            log::debug!("Ignoring paste of {text:?}");
+            self.status = Some(format!("Not a file: {}", path.display()));
+        }
+    }
+
+    // Returns the stretches of sound between silences.
+    fn segments(&self) -> Vec<(Duration, Duration)> {
+        let threshold = 10f32.powf(self.silence_threshold_db / 20.0);
+        Audio::from(&self.source).segments(threshold)
+    }
+
+    // Enters effect mode on the selection, or the whole file without one.
+    fn start_effect(&mut self, effect: Effect) {
+        match &self.mode {
+            Mode::Select(sel) => {
+                self.mode = Mode::Effect {
+                    effect,
+                    selection: sel.to_owned(),
+                    selected: true,
+                };
             }
-            Action::Play => {
-                if self.playing {
-                    log::debug!("Stopping playback");
-                    self.sink.stop();
-                } else {
-                    self.sink
-                        .append(self.source.clone().skip_duration(self.cursor));
-                    log::debug!("Starting playback at {:?}", self.cursor);
-                }
-                self.playing = !self.playing;
+            Mode::Normal => {
+                self.mode = Mode::Effect {
+                    effect,
+                    selection: Selection {
+                        start: Duration::ZERO,
+                        end: self.duration(),
+                    },
+                    selected: false,
+                };
             }
-            Action::ZoomIn => {
-                let len_millis = (self.window_end - self.window_start)
-                    .as_millis()
-                    .saturating_sub(1);
-                let scale_millis = len_millis.ilog10();
-                let zoom_amount = Duration::from_millis(10u64.pow(scale_millis));
-                self.window_end = self.window_end.saturating_sub(zoom_amount);
-                if self.window_end.is_zero() {
-                    self.window_end = Duration::from_millis(1);
+            Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+        }
+    }
+
+    fn save_to(&mut self, path: std::path::PathBuf) {
+        let output = self.output(Audio::from(&self.source));
+        match encode::write(&path, &output, self.wav_bits) {
+            Ok(()) => {
+                log::info!("Saved to {path:?}");
+                // the chain is in the saved audio now, so keep it from being applied again
+                if !self.effect_chain.is_empty() {
+                    log::debug!("Applied {} chained effects", self.effect_chain.len());
+                    self.set_source(output);
+                    self.effect_chain.clear();
                 }
+                self.path = path;
+                self.dirty = false;
             }
-            Action::ZoomOut => {
-                let len_millis = (self.window_end - self.window_start).as_millis();
-                let scale_millis = len_millis.ilog10();
-                let zoom_amount = Duration::from_millis(10u64.pow(scale_millis));
-                self.window_end += zoom_amount;
-            }
-            Action::Select => match self.mode {
-                Mode::Select(_) => {
-                    log::debug!("Ending selection");
-                    self.mode = Mode::Normal
-                }
-                Mode::Normal => {
-                    log::debug!("Started selection");
-                    self.mode = Mode::Select(Selection::new(self.cursor))
-                }
-                Mode::Effect { .. } => {}
-            },
-            Action::SelectAll => match self.mode {
-                Mode::Select(Selection { start, end })
-                    if start.is_zero()
-                        && end >= self.source.total_duration().unwrap_or_default() =>
-                {
-                    log::debug!("Ending selection");
-                    self.mode = Mode::Normal;
-                }
-                _ => {
-                    log::debug!("Selected all");
-                    let end = self.source.total_duration().unwrap_or_default();
-                    self.move_cursor_to(end);
-                    self.mode = Mode::Select(Selection {
-                        start: Duration::ZERO,
-                        end,
-                    });
-                }
-            },
-            Action::Amplify => match &self.mode {
-                Mode::Select(sel) => {
-                    self.mode = Mode::Effect {
-                        effect: Effect::Amplify { amount: 1.0 },
-                        selection: sel.to_owned(),
-                    };
-                }
-                Mode::Normal => {
-                    log::debug!("Cannot apply effect without selection");
-                }
-                Mode::Effect { .. } => {}
-            },
-            Action::Cut => match &self.mode {
-                Mode::Select(sel) => {
-                    let (start, end) = sel.normalize();
-                    log::debug!("Cutting selection ({start:?}, {end:?})");
-                    let source =
-                        std::mem::replace(&mut self.source, SamplesBuffer::new(1, 1, vec![]))
-                            .buffered();
-                    let channels = source.channels();
-                    let sample_rate = source.sample_rate();
-                    let before = source.clone().take_duration(start);
-                    let after = source.skip_duration(end);
-                    let new = before.chain(after);
-                    self.source =
-                        SamplesBuffer::new(channels, sample_rate, new.collect::<Vec<_>>());
-                    self.mode = Mode::Normal;
-                    self.move_cursor_to(start);
-                }
-                Mode::Normal => {
-                    log::debug!("Cannot apply effect without selection");
-                }
-                Mode::Effect { .. } => {}
-            },
-            Action::EffectLeft => match &mut self.mode {
-                Mode::Effect { effect, .. } => {
-                    effect.increase(-0.1);
-                }
-                _ => {}
-            },
-            Action::EffectRight => match &mut self.mode {
-                Mode::Effect { effect, .. } => {
-                    effect.increase(0.1);
-                }
-                _ => {}
-            },
+            Err(err) => log::error!("Failed to save: {err:?}"),
         }
-        Ok(())
     }
 
-    fn handle_events(&mut self) -> Result<()> {
-        if self.playing {
-            self.playhead = self.cursor + self.sink.get_pos();
-            if self.sink.empty() {
-                log::debug!("Done playing");
-                self.playing = false;
+    // Writes the visible part of the audio next to the original, named after the time range.
+    fn export_view(&mut self) {
+        let start = self.window_start;
+        let end = self.window_end.min(self.duration());
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{stem}_{:.3}-{:.3}", start.as_secs_f64(), end.as_secs_f64());
+        if let Some(ext) = self.path.extension() {
+            name = format!("{name}.{}", ext.to_string_lossy());
+        }
+        let path = self.path.with_file_name(name);
+
+        let audio = Audio::from(&self.source);
+        let view = Audio::new(
+            audio.channels,
+            audio.sample_rate,
+            audio.slice(start, end).to_vec(),
+        );
+        match encode::write(&path, &self.output(view), self.wav_bits) {
+            Ok(()) => {
+                log::info!("Exported ({start:?}, {end:?}) to {path:?}");
+                self.status = Some(format!("Exported {}", path.display()));
             }
-            if !event::poll(Duration::from_millis(50))? {
-                return Ok(());
+            Err(err) => {
+                log::error!("Failed to export: {err:?}");
+                self.status = Some(format!("Export failed: {err}"));
             }
         }
-        match event::read()? {
-            // it's important to check that the event is a key press event as
-            // crossterm also emits key release and repeat events on Windows.
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event)?
-            }
-            _ => {}
-        };
-        Ok(())
     }
 
-    fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
-        let Some(actions) = self.binds.apply(key) else {
-            log::trace!("Mapped key to no action");
-            return Ok(());
+    // Appends the reference to the end of the audio, converted to the audio's format, so the
+    // two can be assembled into one file.
+    fn bounce(&mut self) {
+        let Some(reference) = &self.reference else {
+            self.status = Some("Load a reference to bounce".into());
+            return;
         };
-        log::trace!("Mapped key to {actions:?}");
-        for action in actions.clone() {
-            self.apply_action(action)?;
+        let mut audio = Audio::from(&self.source);
+        let reference = record::conform(Audio::from(reference), audio.channels, audio.sample_rate);
+        log::info!(
+            "Bouncing {:?} of reference onto {:?}",
+            reference.duration(),
+            audio.duration()
+        );
+        audio.samples.extend(reference.samples);
+        self.set_source(audio.into());
+        self.status = Some(format!("Bounced to {:.3}s", self.duration().as_secs_f64()));
+    }
+
+    // Appends a copy of the whole file to itself, e.g. to loop it twice.
+    fn duplicate_all(&mut self) {
+        let mut audio = Audio::from(&self.source);
+        if audio.samples.is_empty() {
+            self.status = Some("Nothing to duplicate".into());
+            return;
         }
-        Ok(())
+        log::info!("Duplicating {:?}", audio.duration());
+        audio.samples.extend_from_within(..);
+        self.set_source(audio.into());
+        self.status = Some(format!(
+            "Duplicated to {:.3}s",
+            self.duration().as_secs_f64()
+        ));
     }
-}
+
+    // Keeps only the audio within extract_radius of the cursor.
+    fn extract_around(&mut self) {
+        let start = self.cursor.saturating_sub(self.extract_radius);
+        let end = (self.cursor + self.extract_radius).min(self.duration());
+        log::debug!("Extracting ({start:?}, {end:?})");
+        let audio = Audio::from(&self.source);
+        let samples = audio.slice(start, end).to_vec();
+        self.set_source(Audio::new(audio.channels, audio.sample_rate, samples).into());
+        // keep the cursor on the same audio
+        self.cursor -= start;
+        self.window_start = Duration::ZERO;
+        self.window_end = initial_window_end(self.duration());
+        self.move_cursor_to(self.cursor);
+        self.status = Some(format!("Extracted {:.2}s", self.duration().as_secs_f64()));
+    }
+
+    // Amplifies the selection, or the whole file, so its RMS matches the same span of the
+    // reference. Only the part overlapping the reference is measured.
+    fn match_loudness(&mut self) {
+        let Some(reference) = &self.reference else {
+            self.status = Some("Load a reference to match".into());
+            return;
+        };
+        let reference = Audio::from(reference);
+        let (start, end) = match &self.mode {
+            Mode::Select(sel) => sel.normalize(),
+            Mode::Normal => (Duration::ZERO, self.duration()),
+            Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => return,
+        };
+        let overlap = end.min(reference.duration());
+        let mut audio = Audio::from(&self.source);
+        let (Some(target), Some(rms)) = (
+            reference.rms(start, overlap),
+            audio.rms(start, overlap.max(start)),
+        ) else {
+            self.status = Some("The reference doesn't overlap the selection".into());
+            return;
+        };
+        if target == 0.0 || rms == 0.0 {
+            self.status = Some("Nothing to match".into());
+            return;
+        }
+        let gain = target / rms;
+        let db = 20.0 * gain.log10();
+        log::info!("Matching ({start:?}, {end:?}) to the reference RMS of {target} ({db:+.2}dB)");
+        audio.apply(start, end, &Effect::Amplify { amount: gain });
+        self.set_source(audio.into());
+        self.mode = Mode::Normal;
+        self.status = Some(format!("Matched the reference by {db:+.2}dB"));
+    }
+
+    // Writes the clipboard to a new file next to the audio, so it can be edited on its own.
+    fn paste_as_new(&mut self) {
+        let Some(clip) = &self.clipboard else {
+            self.status = Some("Clipboard is empty".into());
+            return;
+        };
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let path = (1..)
+            .map(|n| self.path.with_file_name(format!("{stem}_clip{n}.wav")))
+            .find(|p| !p.exists())
+            .expect("ran out of clip names");
+        let source = SamplesBuffer::new(clip.channels, clip.sample_rate, clip.samples.clone());
+        match encode::write(&path, &source, self.wav_bits) {
+            Ok(()) => {
+                log::info!("Wrote clipboard to {path:?}");
+                self.status = Some(format!("Pasted into {}", path.display()));
+            }
+            Err(err) => {
+                log::error!("Failed to write clipboard: {err:?}");
+                self.status = Some(format!("Cannot paste as new: {err}"));
+            }
+        }
+    }
+
+    // Writes the markers as an Audacity-style label track next to the audio.
+    fn export_labels(&mut self) {
+        if self.markers.is_empty() {
+            self.status = Some("No markers to export".into());
+            return;
+        }
+        let path = self.path.with_extension("labels.txt");
+        let labels: Vec<_> = self
+            .markers
+            .iter()
+            .map(|m| labels::Label {
+                start: m.pos,
+                end: m.pos,
+                text: m.label.clone().unwrap_or_default(),
+            })
+            .collect();
+        match labels::write(&path, &labels) {
+            Ok(()) => {
+                log::info!("Exported {} labels to {path:?}", labels.len());
+                self.status = Some(format!("Exported {}", path.display()));
+            }
+            Err(err) => {
+                log::error!("Failed to export labels: {err:?}");
+                self.status = Some(format!("Export failed: {err}"));
+            }
+        }
+    }
+
+    // Adds a marker at the start of each label in an Audacity-style label track, and an
+    // unlabelled one at the end of each region. Nothing is added if any line is invalid.
+    fn import_labels(&mut self, path: &std::path::Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        let labels = labels::parse(&text)?;
+        let duration = self.duration();
+        if let Some(label) = labels.iter().find(|l| l.end > duration) {
+            bail!(
+                "{:.3}s is past the end of the audio",
+                label.end.as_secs_f64()
+            );
+        }
+        for label in &labels {
+            self.add_marker(Marker {
+                pos: label.start,
+                label: (!label.text.is_empty()).then(|| label.text.clone()),
+                category: Category::Plain,
+            });
+            if label.end > label.start {
+                self.add_marker(Marker {
+                    pos: label.end,
+                    label: None,
+                    category: Category::Plain,
+                });
+            }
+        }
+        log::info!("Imported {} labels from {path:?}", labels.len());
+        self.status = Some(format!("Imported {} labels", labels.len()));
+        Ok(())
+    }
+
+    fn is_whole_file(&self, selection: &Selection) -> bool {
+        let (start, end) = selection.normalize();
+        start.is_zero() && end >= self.duration()
+    }
+
+    fn apply_effect(&mut self, selection: Selection, effect: Effect) {
+        // always apply to the current source, so earlier edits are built upon
+        let (start, end) = selection.normalize();
+        log::debug!("Applying {} to ({start:?}, {end:?})", effect.describe());
+        let stacked = match (self.stacked_gain, &effect) {
+            (Some((region, gain)), Effect::Amplify { amount }) if region == (start, end) => {
+                Some(gain * amount)
+            }
+            _ => None,
+        };
+        let mut audio = Audio::from(&self.source);
+        audio.apply(start, end, &effect);
+        self.set_source(audio.into());
+
+        let mut status = format!("Applied {} (edit {})", effect.describe(), self.edits);
+        if let Some(gain) = stacked {
+            let db = 20.0 * gain.log10();
+            status = format!("{status}, {db:+.1}dB total on this selection");
+            if db > STACKED_GAIN_WARNING_DB {
+                log::warn!("Amplified selection by {db:+.1}dB in total");
+            }
+        }
+        self.status = Some(status);
+        self.stacked_gain = match effect {
+            Effect::Amplify { amount } => Some(((start, end), stacked.unwrap_or(amount))),
+            Effect::Echo { .. } | Effect::Gate { .. } => None,
+        };
+    }
+
+    // Applies the effect before and after the selection, leaving the selection untouched.
+    fn apply_to_complement(&mut self, selection: Selection, effect: Effect) {
+        let (start, end) = selection.normalize();
+        log::debug!(
+            "Applying {} outside of ({start:?}, {end:?})",
+            effect.describe()
+        );
+        let mut audio = Audio::from(&self.source);
+        audio.apply(Duration::ZERO, start, &effect);
+        audio.apply(end, audio.duration(), &effect);
+        self.set_source(audio.into());
+        self.stacked_gain = None;
+        self.status = Some(format!(
+            "Applied {} outside the selection (edit {})",
+            effect.describe(),
+            self.edits
+        ));
+    }
+
+    fn normalize_file(&mut self) {
+        let mut audio = Audio::from(&self.source);
+        let peak = 10f32.powf(self.normalize_dbfs / 20.0);
+        match audio.normalize(Duration::ZERO, audio.duration(), peak) {
+            Some(gain) => {
+                let db = 20.0 * gain.log10();
+                log::info!(
+                    "Normalized file to {}dBFS ({db:+.2}dB)",
+                    self.normalize_dbfs
+                );
+                self.status = Some(format!("Normalized by {db:+.2}dB"));
+                self.set_source(audio.into());
+            }
+            None => self.status = Some("Nothing to normalize".into()),
+        }
+    }
+
+    fn downmix(&mut self) {
+        log::debug!("Downmixing {} channels to mono", self.source.channels());
+        self.set_source(Audio::from(&self.source).downmix().into());
+    }
+
+    fn accept_confirm(&mut self) {
+        let Mode::Confirm(confirm) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return;
+        };
+        match confirm {
+            Confirm::Overwrite(path) => self.save_to(path),
+            Confirm::Revert => self.revert(),
+            Confirm::Open(path) => self.open(path),
+            Confirm::Quit => {
+                self.save_to(self.path.clone());
+                // Stay open if saving failed so the changes aren't lost.
+                self.exit = !self.dirty;
+            }
+            Confirm::Normalize => self.busy("Normalizing", App::normalize_file),
+            Confirm::Downmix => self.busy("Downmixing", App::downmix),
+            Confirm::ExtractAround => self.extract_around(),
+            Confirm::MatchLoudness => self.match_loudness(),
+            Confirm::ApplyEffect {
+                selection, effect, ..
+            } => self.busy("Applying", move |app| app.apply_effect(selection, effect)),
+        }
+    }
+
+    // Reloads the file, discarding all edits and the undo history.
+    fn revert(&mut self) {
+        match self.reload(self.path.clone()) {
+            Ok(()) => self.status = Some(format!("Reverted to {}", self.path.display())),
+            Err(err) => {
+                log::error!("Failed to revert: {err:?}");
+                self.status = Some(format!("Cannot revert: {err}"));
+            }
+        }
+    }
+
+    // Replaces the audio with another file, which is where it will be saved.
+    fn open_file(&mut self, path: std::path::PathBuf) {
+        if self.dirty {
+            self.mode = Mode::Confirm(Confirm::Open(path));
+        } else {
+            self.open(path);
+        }
+    }
+
+    fn open(&mut self, path: std::path::PathBuf) {
+        match self.reload(path) {
+            Ok(()) => {
+                self.markers.clear();
+                self.jumped_from = None;
+                self.last_jump = None;
+                self.status = Some(format!("Opened {}", self.path.display()));
+            }
+            Err(err) => {
+                log::error!("Failed to open: {err:?}");
+                self.status = Some(format!("Cannot open: {err}"));
+            }
+        }
+    }
+
+    // Loads the audio at path, discarding all edits and the undo history.
+    fn reload(&mut self, path: std::path::PathBuf) -> Result<()> {
+        let source = load(&path)?;
+        log::info!("Loading {path:?}");
+        self.path = path;
+        self.sink.stop();
+        self.playing = false;
+        self.looping = None;
+        self.loop_region = None;
+        self.loop_all = false;
+        self.effect_chain.clear();
+        self.reference = None;
+        self.source = source;
+        self.undo.clear();
+        self.redo.clear();
+        self.wave_cache.take();
+        self.overview_cache.take();
+        self.dirty = false;
+        self.mode = Mode::Normal;
+        self.cursor = Duration::ZERO;
+        self.window_start = Duration::ZERO;
+        self.window_end = initial_window_end(self.duration());
+        Ok(())
+    }
+
+    fn cancel_confirm(&mut self) {
+        let Mode::Confirm(confirm) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return;
+        };
+        match confirm {
+            Confirm::Overwrite(path) => {
+                log::debug!("Not overwriting {path:?}");
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::SaveAs,
+                    input: path.display().to_string(),
+                };
+            }
+            Confirm::Revert => log::debug!("Not reverting"),
+            Confirm::Open(path) => log::debug!("Not opening {path:?}"),
+            Confirm::Quit => log::debug!("Not quitting"),
+            Confirm::Normalize => log::debug!("Not normalizing"),
+            Confirm::Downmix => log::debug!("Not downmixing"),
+            Confirm::ExtractAround => log::debug!("Not extracting"),
+            Confirm::MatchLoudness => log::debug!("Not matching loudness"),
+            Confirm::ApplyEffect {
+                selection,
+                effect,
+                selected,
+            } => {
+                log::debug!("Not applying {}", effect.describe());
+                self.mode = Mode::Effect {
+                    selection,
+                    effect,
+                    selected,
+                };
+            }
+        }
+    }
+
+    // Returns the audio to send to the output when playing from the given position.
+    fn playback_source(&self, from: Duration) -> SamplesBuffer<f32> {
+        self.playback_range(from, self.duration())
+    }
+
+    fn playback_range(&self, start: Duration, end: Duration) -> SamplesBuffer<f32> {
+        let mut audio = self.region(start, end);
+        audio.micro_fade(Duration::ZERO, audio.duration(), self.anti_click);
+        self.monitor(audio, start)
+    }
+
+    // Returns one pass of the region between start and end, to be played on repeat.
+    fn loop_source(&self, start: Duration, end: Duration) -> SamplesBuffer<f32> {
+        let mut audio = self.region(start, end);
+        audio.crossfade_loop(self.loop_crossfade);
+        self.monitor(audio, start)
+    }
+
+    fn region(&self, start: Duration, end: Duration) -> Audio {
+        let channels = self.source.channels() as usize;
+        let (start, end) = (self.frame_at(start) as usize, self.frame_at(end) as usize);
+        let samples = self
+            .source
+            .clone()
+            .skip(start * channels)
+            .take(end.saturating_sub(start) * channels)
+            .collect();
+        Audio::new(self.source.channels(), self.source.sample_rate(), samples)
+    }
+
+    // Applies the monitoring options to audio that is about to be played from start.
+    fn monitor(&self, mut audio: Audio, start: Duration) -> SamplesBuffer<f32> {
+        if self.mono_monitor {
+            audio = audio.downmix();
+        }
+        if let Some(beat) = self.beat().filter(|_| self.click) {
+            audio.add_clicks(start, beat);
+        }
+        if self.limit_playback {
+            audio
+                .samples
+                .iter_mut()
+                .for_each(|v| *v = edit::soft_clip(*v));
+        }
+        audio.into()
+    }
+
+    // Converts audio for the sink to the output's sample rate as it's played, so only what is
+    // actually heard gets resampled.
+    fn to_output<S>(&self, source: S) -> UniformSourceIterator<S, f32>
+    where
+        S: Source<Item = f32>,
+    {
+        let (channels, rate) = (source.channels(), source.sample_rate());
+        UniformSourceIterator::new(source, channels, self.output_rate.unwrap_or(rate))
+    }
+
+    // Plays a grain at the cursor, replacing any grain that hasn't finished yet.
+    fn scrub(&mut self) {
+        if !self.scrub || self.playing {
+            return;
+        }
+        let grain = self.playback_range(self.cursor, self.cursor + SCRUB_GRAIN);
+        log::trace!(
+            "Scrubbing {:?} at {:?}",
+            grain.total_duration(),
+            self.cursor
+        );
+        self.sink.stop();
+        self.sink.append(self.to_output(grain));
+    }
+
+    // Returns the index of the frame nearest to pos, rounding the same way edits do.
+    fn frame_at(&self, pos: Duration) -> u128 {
+        edit::nearest_frame(pos, self.source.sample_rate())
+    }
+
+    // Describes the selected range as times and frame indices.
+    fn selection_readout(&self) -> Option<String> {
+        let (start, end) = match &self.mode {
+            Mode::Select(sel) | Mode::Effect { selection: sel, .. } => sel.normalize(),
+            Mode::Normal | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => return None,
+        };
+        Some(format!(
+            "{:.3}s - {:.3}s (samples {} - {})",
+            start.as_secs_f64(),
+            end.as_secs_f64(),
+            self.frame_at(start),
+            self.frame_at(end),
+        ))
+    }
+
+    // Returns the beat length if the cursor should snap to it.
+    fn snap_beat(&self) -> Option<Duration> {
+        self.beat().filter(|_| self.snap)
+    }
+
+    // Describes the frame under the cursor, with one value per channel.
+    fn sample_readout(&self) -> Option<String> {
+        if !self.sample_readout {
+            return None;
+        }
+        let frame = self.frame_at(self.cursor) as usize;
+        let channels = self.source.channels() as usize;
+        let values: Vec<_> = self
+            .source
+            .clone()
+            .skip(frame * channels)
+            .take(channels)
+            .map(|v| format!("{v:+.4}"))
+            .collect();
+        if values.is_empty() {
+            Some(format!("sample {frame}: end"))
+        } else {
+            Some(format!("sample {frame}: {}", values.join(" ")))
+        }
+    }
+
+    fn beat(&self) -> Option<Duration> {
+        self.bpm
+            .filter(|bpm| *bpm > 0.0)
+            .map(|bpm| Duration::from_secs_f64(60.0 / bpm))
+    }
+
+    // Records a tap at now, setting the bpm from the average interval between recent taps.
+    fn tap_tempo(&mut self, now: Instant) {
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now.saturating_duration_since(*last) > TAP_TIMEOUT)
+        {
+            log::debug!("Tap tempo timed out, starting over");
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > TAP_COUNT {
+            self.taps.remove(0);
+        }
+        let (Some(first), Some(last)) = (self.taps.first(), self.taps.last()) else {
+            return;
+        };
+        let intervals = self.taps.len() as u32 - 1;
+        if intervals == 0 {
+            self.status = Some("Tap again to set the tempo".into());
+            return;
+        }
+        let beat = last.saturating_duration_since(*first) / intervals;
+        if beat.is_zero() {
+            return;
+        }
+        let bpm = 60.0 / beat.as_secs_f64();
+        log::debug!("Tapped {bpm:.1}bpm over {intervals} intervals");
+        self.bpm = Some(bpm);
+        self.wave_cache.take();
+        self.status = Some(format!("{bpm:.1} bpm"));
+    }
+
+    // Reports the time between the markers either side of the cursor, or the two nearest it if
+    // the cursor is before the first or after the last, and the bpm if that were one beat.
+    fn measure_interval(&mut self) {
+        if self.markers.len() < 2 {
+            self.status = Some("Add two markers to measure between".into());
+            return;
+        }
+        let idx = self.markers.partition_point(|m| m.pos <= self.cursor);
+        let idx = idx.clamp(1, self.markers.len() - 1);
+        let interval = self.markers[idx].pos - self.markers[idx - 1].pos;
+        log::debug!(
+            "Interval between markers {} and {idx}: {interval:?}",
+            idx - 1
+        );
+        self.status = Some(if interval.is_zero() {
+            "The markers are at the same position".into()
+        } else {
+            let bpm = 60.0 / interval.as_secs_f64();
+            format!("{:.3}s ({bpm:.1} bpm)", interval.as_secs_f64())
+        });
+    }
+
+    fn add_marker(&mut self, marker: Marker) {
+        log::debug!("Adding marker {:?} at {:?}", marker.label, marker.pos);
+        let idx = self.markers.partition_point(|m| m.pos <= marker.pos);
+        self.markers.insert(idx, marker);
+    }
+
+    // Finds the first marker at or after from whose label contains query, wrapping around to
+    // the start if there is none.
+    fn find_marker(&self, query: &str, from: Duration) -> Option<Duration> {
+        if query.is_empty() {
+            return None;
+        }
+        let query = query.to_lowercase();
+        let mut matches = self.markers.iter().filter(|m| {
+            m.label
+                .as_ref()
+                .is_some_and(|l| l.to_lowercase().contains(&query))
+        });
+        let first = matches.clone().next();
+        matches.find(|m| m.pos >= from).or(first).map(|m| m.pos)
+    }
+
+    // Called whenever the prompt input changes.
+    fn update_prompt(&mut self) {
+        let Mode::Prompt {
+            prompt: Prompt::SearchMarker { origin },
+            input,
+        } = &self.mode
+        else {
+            return;
+        };
+        let pos = self.find_marker(input, *origin).unwrap_or(*origin);
+        self.move_cursor_to(pos);
+    }
+
+    fn cancel_prompt(&mut self) {
+        let Mode::Prompt { prompt, .. } = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return;
+        };
+        if let Prompt::SearchMarker { origin } = prompt {
+            self.move_cursor_to(origin);
+        }
+    }
+
+    fn submit_prompt(&mut self) -> Result<()> {
+        let Mode::Prompt { prompt, input } = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return Ok(());
+        };
+        log::debug!("Submitted prompt: {input:?}");
+        match prompt {
+            Prompt::InsertTone => {
+                let mut args = input.split_whitespace().map(str::parse::<f64>);
+                let (Some(Ok(freq)), Some(Ok(secs)), None) =
+                    (args.next(), args.next(), args.next())
+                else {
+                    log::warn!("Expected '<hz> <secs>', got {input:?}");
+                    return Ok(());
+                };
+                if freq < 0.0 || secs <= 0.0 {
+                    log::warn!("Invalid tone: {freq}hz for {secs}s");
+                    return Ok(());
+                }
+                let duration = Duration::from_secs_f64(secs);
+                log::debug!(
+                    "Inserting {freq}hz tone for {duration:?} at {:?}",
+                    self.cursor
+                );
+                let samples = edit::tone(
+                    freq as f32,
+                    duration,
+                    self.source.channels(),
+                    self.source.sample_rate(),
+                );
+                let at = self.cursor;
+                self.splice(at, at, samples);
+                self.move_cursor_to(at + duration);
+            }
+            Prompt::GoToSample => {
+                let frames = Audio::from(&self.source).frames() as u64;
+                let index = match input.trim().parse::<u64>() {
+                    Ok(index) if index <= frames => index,
+                    Ok(index) => {
+                        log::warn!("Sample {index} is past the end ({frames})");
+                        self.status = Some(format!("Sample must be at most {frames}"));
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        log::warn!("Invalid sample index {input:?}: {err}");
+                        self.status = Some(format!("Not a sample index: {input:?}"));
+                        return Ok(());
+                    }
+                };
+                // round up, so the frame at the cursor is the one asked for
+                let rate = self.source.sample_rate() as u128;
+                let nanos = (index as u128 * 1_000_000_000).div_ceil(rate);
+                let pos = Duration::from_nanos(nanos as u64);
+                log::debug!("Going to sample {index} at {pos:?}");
+                self.jump_cursor_to(pos);
+            }
+            Prompt::SaveAs => {
+                let path = std::path::PathBuf::from(input.trim());
+                if path.as_os_str().is_empty() {
+                    log::warn!("No path given to save as");
+                } else if path.exists() {
+                    log::debug!("{path:?} exists, confirming overwrite");
+                    self.mode = Mode::Confirm(Confirm::Overwrite(path));
+                } else {
+                    self.save_to(path);
+                }
+            }
+            Prompt::Reference => {
+                let path = std::path::Path::new(input.trim());
+                if path.as_os_str().is_empty() {
+                    log::debug!("Clearing reference");
+                    self.reference = None;
+                    return Ok(());
+                }
+                self.load_reference(path);
+            }
+            Prompt::ImportLabels => {
+                let path = std::path::Path::new(input.trim());
+                if let Err(err) = self.import_labels(path) {
+                    log::error!("Failed to import labels: {err:?}");
+                    self.status = Some(format!("Cannot import labels: {err}"));
+                }
+            }
+            Prompt::MarkerLabel { pos } => {
+                let label = input.trim();
+                self.add_marker(Marker {
+                    pos,
+                    label: (!label.is_empty()).then(|| label.to_string()),
+                    category: Category::Plain,
+                });
+            }
+            Prompt::SearchMarker { origin } => {
+                if self.find_marker(&input, origin).is_none() {
+                    log::info!("No marker matching {input:?}");
+                } else {
+                    self.jumped_from = Some(origin);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_action(&mut self, action: Action) -> Result<()> {
+        log::trace!("Applying action: {action:?}");
+        match action {
+            Action::Quit if self.dirty => {
+                log::debug!("Unsaved changes, confirming quit");
+                self.mode = Mode::Confirm(Confirm::Quit);
+            }
+            Action::Quit | Action::ForceQuit => {
+                log::info!("Exit requested");
+                self.exit = true;
+            }
+            Action::Save => {
+                self.save_to(self.path.clone());
+            }
+            Action::ExportView => self.busy("Exporting", App::export_view),
+            Action::ExportLabels => self.export_labels(),
+            Action::PasteAsNew => self.paste_as_new(),
+            Action::ImportLabels => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::ImportLabels,
+                    input: self.path.with_extension("labels.txt").display().to_string(),
+                };
+            }
+            Action::Revert => self.mode = Mode::Confirm(Confirm::Revert),
+            // Unlike Revert, this keeps the audio and edits.
+            Action::ResetView => {
+                log::debug!("Resetting the view");
+                self.mode = Mode::Normal;
+                self.log_time = false;
+                self.cursor = Duration::ZERO;
+                self.window_start = Duration::ZERO;
+                self.window_end = initial_window_end(self.duration());
+            }
+            Action::OpenFile => {
+                let dir = match self.path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => std::path::Path::new("."),
+                };
+                match Picker::open(dir) {
+                    Ok(picker) => self.mode = Mode::Pick(picker),
+                    Err(err) => {
+                        log::error!("Failed to list files: {err:?}");
+                        self.status = Some(format!("Cannot list files: {err}"));
+                    }
+                }
+            }
+            Action::SaveAs => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::SaveAs,
+                    input: String::new(),
+                };
+            }
+            Action::CursorLeft => {
+                let pos = match self.snap_beat() {
+                    // step to the previous gridline rather than snapping back to this one
+                    Some(beat) => {
+                        let beats = self.cursor.as_nanos().div_ceil(beat.as_nanos());
+                        beat * beats.saturating_sub(1) as u32
+                    }
+                    None => self.cursor.saturating_sub(Duration::from_millis(10)),
+                };
+                self.move_cursor_to(pos);
+                self.scrub();
+            }
+            Action::CursorRight => {
+                let pos = match self.snap_beat() {
+                    Some(beat) => {
+                        let beats = self.cursor.as_nanos() / beat.as_nanos();
+                        beat * (beats + 1) as u32
+                    }
+                    None => self.cursor.saturating_add(Duration::from_millis(10)),
+                };
+                self.move_cursor_to(pos);
+                self.scrub();
+            }
+            Action::ToggleSnap => {
+                self.snap = !self.snap;
+                let state = if self.snap { "on" } else { "off" };
+                self.status = Some(format!("Snap to grid {state}"));
+            }
+            Action::ToggleSampleReadout => {
+                self.sample_readout = !self.sample_readout;
+            }
+            Action::ToggleScrub => {
+                self.scrub = !self.scrub;
+                log::debug!("Scrubbing: {}", self.scrub);
+            }
+            Action::CursorStart => {
+                self.jump_cursor_to(Duration::ZERO);
+            }
+            Action::CursorEnd => {
+                self.jump_cursor_to(self.duration());
+            }
+            Action::SeekPercent(pct) => {
+                let pct = pct.min(100) as u32;
+                self.jump_cursor_to(self.duration() * pct / 100);
+            }
+            Action::JumpBack => match self.jumped_from {
+                Some(pos) => {
+                    log::debug!("Jumping back to {pos:?}");
+                    self.jump_cursor_to(pos);
+                }
+                None => self.status = Some("No previous position".into()),
+            },
+            // Unlike JumpBack, this moves by the opposite of the last jump, so it can repeat.
+            Action::NavBack => match self.last_jump {
+                Some((from, to)) => {
+                    let pos = if to > from {
+                        self.cursor.saturating_sub(to - from)
+                    } else {
+                        self.cursor + (from - to)
+                    };
+                    log::debug!("Reversing jump from {from:?} to {to:?}, moving to {pos:?}");
+                    self.jumped_from = Some(self.cursor);
+                    self.move_cursor_to(pos);
+                }
+                None => self.status = Some("No previous jump".into()),
+            },
+            Action::Play => {
+                if self.playing {
+                    log::debug!("Stopping playback");
+                    self.sink.stop();
+                } else if self.duration() <= self.cursor && !self.rewind_for_play() {
+                    self.status = Some("Nothing to play".into());
+                    return Ok(());
+                } else {
+                    // drop any scrub grain still queued so the playhead lines up
+                    self.sink.stop();
+                    self.sink
+                        .append(self.to_output(self.playback_source(self.cursor)));
+                    log::debug!("Starting playback at {:?}", self.cursor);
+                }
+                self.looping = None;
+                self.play_clock = None;
+                self.playing = !self.playing;
+            }
+            Action::PlayLoop => {
+                self.sink.stop();
+                if self.playing {
+                    log::debug!("Stopping playback");
+                    self.playing = false;
+                    self.looping = None;
+                    return Ok(());
+                }
+                let (start, end) = match &self.mode {
+                    Mode::Select(sel) => sel.normalize(),
+                    _ => self
+                        .loop_region
+                        .unwrap_or((Duration::ZERO, self.duration())),
+                };
+                let source = self.loop_source(start, end);
+                let Some(len) = source.total_duration().filter(|d| !d.is_zero()) else {
+                    self.status = Some("Nothing to play".into());
+                    return Ok(());
+                };
+                log::debug!("Looping ({start:?}, {end:?})");
+                self.sink.append(self.to_output(source.repeat_infinite()));
+                self.looping = Some((start, len));
+                self.play_clock = None;
+                self.playing = true;
+            }
+            Action::SelectionToLoop => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    log::debug!("Setting loop region ({start:?}, {end:?})");
+                    self.loop_region = Some((start, end));
+                    self.status = Some(format!(
+                        "Looping {:.3}s - {:.3}s",
+                        start.as_secs_f64(),
+                        end.as_secs_f64()
+                    ));
+                    self.mode = Mode::Normal;
+                }
+                // without a selection, go back to looping the whole file
+                Mode::Normal => {
+                    log::debug!("Clearing loop region");
+                    self.loop_region = None;
+                    self.status = Some("Looping the whole file".into());
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::CopyTimecode => {
+                let timecode = timecode(self.cursor);
+                match osc52::copy(&mut self.text_clipboard, &timecode) {
+                    Ok(()) => self.status = Some(format!("Copied {timecode}")),
+                    Err(err) => {
+                        log::error!("Failed to copy {timecode}: {err:?}");
+                        self.status = Some(format!("Cannot copy {timecode}: {err}"));
+                    }
+                }
+            }
+            Action::ToggleLoopAll => {
+                self.loop_all = !self.loop_all;
+                log::debug!("Loop all: {}", self.loop_all);
+                self.status = Some(format!(
+                    "Loop all {}",
+                    if self.loop_all { "on" } else { "off" }
+                ));
+            }
+            Action::CenterOnCursor => self.center_on(self.cursor),
+            Action::Record => match self.recorder.take() {
+                Some(recorder) => {
+                    log::info!("Stopped recording");
+                    self.insert_recording(recorder.stop());
+                }
+                None => match Recorder::start() {
+                    Ok(recorder) => self.recorder = Some(recorder),
+                    Err(err) => {
+                        log::error!("Failed to start recording: {err:?}");
+                        self.status = Some(format!("Cannot record: {err}"));
+                    }
+                },
+            },
+            Action::ZoomIn => {
+                let len_millis = (self.window_end - self.window_start)
+                    .as_millis()
+                    .saturating_sub(1);
+                let Some(scale_millis) = len_millis.checked_ilog10() else {
+                    log::debug!("Cannot zoom in any further");
+                    return Ok(());
+                };
+                let zoom_amount = Duration::from_millis(10u64.pow(scale_millis));
+                self.window_end = self.window_end.saturating_sub(zoom_amount);
+                self.clamp_window();
+            }
+            Action::ZoomOut => {
+                let len_millis = (self.window_end - self.window_start).as_millis();
+                let scale_millis = len_millis.checked_ilog10().unwrap_or(0);
+                let zoom_amount = Duration::from_millis(10u64.pow(scale_millis));
+                self.window_end += zoom_amount;
+                self.clamp_window();
+            }
+            Action::ZoomInFast | Action::ZoomOutFast => {
+                let factor = if matches!(action, Action::ZoomInFast) {
+                    self.fast_zoom.recip()
+                } else {
+                    self.fast_zoom
+                };
+                let width = (self.window_end - self.window_start).mul_f64(factor);
+                log::debug!("Zooming to {width:?}");
+                self.window_end = self.window_start + width;
+                self.clamp_window();
+            }
+            Action::ZoomWidth(_) | Action::ZoomOneSecond => {
+                let width = match action {
+                    Action::ZoomWidth(ms) => Duration::from_millis(ms),
+                    _ => Duration::from_secs(1),
+                };
+                log::debug!("Zooming to {width:?} around {:?}", self.cursor);
+                self.window_start = self.cursor.saturating_sub(width / 2);
+                self.window_end = self.window_start + width;
+                self.clamp_window();
+            }
+            Action::Select => match self.mode {
+                Mode::Select(_) => {
+                    log::debug!("Ending selection");
+                    self.mode = Mode::Normal
+                }
+                Mode::Normal => {
+                    log::debug!("Started selection");
+                    self.mode = Mode::Select(Selection::new(self.cursor))
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::ClearSelection => match std::mem::replace(&mut self.mode, Mode::Normal) {
+                // cancel the effect, keeping the selection to try another
+                Mode::Effect {
+                    selection,
+                    selected: true,
+                    ..
+                } => {
+                    log::debug!("Cancelling effect");
+                    self.mode = Mode::Select(selection);
+                }
+                _ => log::debug!("Clearing selection"),
+            },
+            Action::SelectAll => match self.mode {
+                Mode::Select(Selection { start, end })
+                    if start.is_zero() && end >= self.duration() =>
+                {
+                    log::debug!("Ending selection");
+                    self.mode = Mode::Normal;
+                }
+                _ => {
+                    log::debug!("Selected all");
+                    let end = self.duration();
+                    self.move_cursor_to(end);
+                    self.mode = Mode::Select(Selection {
+                        start: Duration::ZERO,
+                        end,
+                    });
+                }
+            },
+            Action::ExtendSelectionBeat => {
+                let Some(beat) = self.beat() else {
+                    log::info!("Cannot extend selection by a beat without a bpm");
+                    return Ok(());
+                };
+                let (start, end) = match &self.mode {
+                    Mode::Select(sel) => sel.normalize(),
+                    Mode::Normal => (self.cursor, self.cursor),
+                    Mode::Effect { .. }
+                    | Mode::Prompt { .. }
+                    | Mode::Confirm(_)
+                    | Mode::Pick(_) => {
+                        return Ok(());
+                    }
+                };
+                log::debug!("Extending selection ({start:?}, {end:?}) by {beat:?}");
+                self.mode = Mode::Select(Selection::new(start));
+                self.move_cursor_to(end + beat);
+            }
+            Action::SnapToFrames => {
+                let Mode::Select(sel) = &self.mode else {
+                    log::info!("Nothing selected to snap to frames");
+                    return Ok(());
+                };
+                if self.fps <= 0.0 {
+                    self.status = Some(format!("Invalid fps {}", self.fps));
+                    return Ok(());
+                }
+                let fps = self.fps;
+                let duration = self.duration();
+                let to_frame = |pos: Duration| (pos.as_secs_f64() * fps).round();
+                let to_pos = |frame: f64| Duration::from_secs_f64(frame / fps).min(duration);
+                let (start, end) = (to_frame(sel.start), to_frame(sel.end));
+                log::debug!("Snapping selection to frames ({start}, {end}) at {fps}fps");
+                // the cursor is the moving edge of the selection
+                self.cursor = to_pos(start);
+                self.mode = Mode::Select(Selection {
+                    start: self.cursor,
+                    end: to_pos(end),
+                });
+                self.status = Some(format!(
+                    "Selected frames {}-{} at {fps}fps",
+                    start.min(end),
+                    start.max(end)
+                ));
+            }
+            Action::Amplify => self.start_effect(Effect::Amplify { amount: 1.0 }),
+            Action::Echo => self.start_effect(Effect::Echo {
+                delay: 0.25,
+                feedback: 0.5,
+            }),
+            Action::Gate => self.start_effect(Effect::Gate {
+                threshold_db: self.silence_threshold_db,
+                attack: 0.005,
+                release: 0.05,
+            }),
+            Action::Cut => match &self.mode {
+                Mode::Select(sel) => {
+                    let (mut start, mut end) = sel.normalize();
+                    if self.snap_cuts_to_zero {
+                        let audio = Audio::from(&self.source);
+                        start = audio.nearest_zero_crossing(start);
+                        end = audio.nearest_zero_crossing(end).max(start);
+                    }
+                    log::debug!("Cutting selection ({start:?}, {end:?})");
+                    self.splice(start, end, vec![]);
+                    self.mode = Mode::Normal;
+                    self.move_cursor_to(start);
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot apply effect without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::ExtractAround => {
+                match self.mode {
+                    Mode::Normal | Mode::Select(_) => self.mode = Mode::Normal,
+                    Mode::Effect { .. }
+                    | Mode::Prompt { .. }
+                    | Mode::Confirm(_)
+                    | Mode::Pick(_) => {
+                        return Ok(());
+                    }
+                }
+                if self.confirm_destructive {
+                    self.mode = Mode::Confirm(Confirm::ExtractAround);
+                } else {
+                    self.extract_around();
+                }
+            }
+            Action::MicroFade => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    log::debug!("Fading edges of ({start:?}, {end:?})");
+                    let mut audio = Audio::from(&self.source);
+                    audio.micro_fade(start, end, self.micro_fade);
+                    self.set_source(audio.into());
+                    self.mode = Mode::Normal;
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot fade without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::NudgeContentLeft | Action::NudgeContentRight => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    let frames = self.frame_at(NUDGE) as isize;
+                    let frames = if matches!(action, Action::NudgeContentLeft) {
+                        -frames
+                    } else {
+                        frames
+                    };
+                    log::debug!("Nudging ({start:?}, {end:?}) by {frames} frames");
+                    let mut audio = Audio::from(&self.source);
+                    audio.shift(start, end, frames);
+                    self.set_source(audio.into());
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot nudge without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::Copy => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    log::debug!("Copying selection ({start:?}, {end:?})");
+                    let audio = Audio::from(&self.source);
+                    self.clipboard = Some(Audio::new(
+                        audio.channels,
+                        audio.sample_rate,
+                        audio.slice(start, end).to_vec(),
+                    ));
+                    self.mode = Mode::Normal;
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot copy without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::DuplicateSelection => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    log::debug!("Duplicating selection ({start:?}, {end:?})");
+                    let audio = Audio::from(&self.source);
+                    let copy = audio.slice(start, end).to_vec();
+                    self.splice(end, end, copy);
+                    self.mode = Mode::Normal;
+                    self.move_cursor_to(end + (end - start));
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot duplicate without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::Paste => {
+                let Some(clip) = self.clip() else {
+                    return Ok(());
+                };
+                let (at, len) = (self.cursor, clip.duration());
+                log::debug!("Pasting {len:?} at {at:?}");
+                self.splice(at, at, clip.samples);
+                self.move_cursor_to(at + len);
+            }
+            Action::ReplaceSelection => match &self.mode {
+                Mode::Select(sel) => {
+                    let (start, end) = sel.normalize();
+                    let Some(clip) = self.clip() else {
+                        return Ok(());
+                    };
+                    let len = clip.duration();
+                    log::debug!("Replacing selection ({start:?}, {end:?}) with {len:?}");
+                    self.splice(start, end, clip.samples);
+                    self.mode = Mode::Normal;
+                    self.move_cursor_to(start + len);
+                }
+                Mode::Normal => {
+                    log::debug!("Cannot replace without selection");
+                }
+                Mode::Effect { .. } | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+            },
+            Action::EffectLeft => {
+                if let Mode::Effect { effect, .. } = &mut self.mode {
+                    effect.increase(-self.effect_step);
+                }
+            }
+            Action::EffectRight => {
+                if let Mode::Effect { effect, .. } = &mut self.mode {
+                    effect.increase(self.effect_step);
+                }
+            }
+            Action::EffectLeftFine => {
+                if let Mode::Effect { effect, .. } = &mut self.mode {
+                    effect.increase(-self.fine_effect_step);
+                }
+            }
+            Action::EffectRightFine => {
+                if let Mode::Effect { effect, .. } = &mut self.mode {
+                    effect.increase(self.fine_effect_step);
+                }
+            }
+            Action::EffectDown | Action::EffectUp => {
+                if let Mode::Effect { effect, .. } = &mut self.mode {
+                    let (primary, secondary) = effect.params();
+                    if secondary.is_none() {
+                        self.status = Some(format!("Only the {primary} can be adjusted"));
+                    } else if matches!(action, Action::EffectUp) {
+                        effect.increase_secondary(self.effect_step);
+                    } else {
+                        effect.increase_secondary(-self.effect_step);
+                    }
+                }
+            }
+            Action::ApplyEffect => {
+                let Mode::Effect {
+                    selection,
+                    effect,
+                    selected,
+                } = std::mem::replace(&mut self.mode, Mode::Normal)
+                else {
+                    return Ok(());
+                };
+                if self.confirm_destructive && self.is_whole_file(&selection) {
+                    self.mode = Mode::Confirm(Confirm::ApplyEffect {
+                        selection,
+                        effect,
+                        selected,
+                    });
+                    return Ok(());
+                }
+                if self.is_whole_file(&selection) {
+                    self.busy("Applying", move |app| app.apply_effect(selection, effect));
+                } else {
+                    self.apply_effect(selection, effect);
+                }
+            }
+            Action::ApplyToComplement => match std::mem::replace(&mut self.mode, Mode::Normal) {
+                Mode::Effect {
+                    selection,
+                    effect,
+                    selected: true,
+                } => self.apply_to_complement(selection, effect),
+                mode => {
+                    self.status = Some("Select a region to apply outside of".into());
+                    self.mode = mode;
+                }
+            },
+            Action::PushEffect => match std::mem::replace(&mut self.mode, Mode::Normal) {
+                Mode::Effect {
+                    effect,
+                    selected: false,
+                    ..
+                } => {
+                    log::debug!("Adding {} to the effect chain", effect.describe());
+                    self.status = Some(format!("Added {} to the effect chain", effect.describe()));
+                    self.effect_chain.push(effect);
+                    self.dirty = true;
+                }
+                mode => {
+                    if matches!(mode, Mode::Effect { .. }) {
+                        self.status = Some(
+                            "The effect chain applies to the whole file, not a selection".into(),
+                        );
+                    }
+                    self.mode = mode;
+                }
+            },
+            Action::PopEffect => match self.effect_chain.pop() {
+                Some(effect) => {
+                    log::debug!("Removed {} from the effect chain", effect.describe());
+                    self.status = Some(format!(
+                        "Removed {} from the effect chain",
+                        effect.describe()
+                    ));
+                    self.dirty = true;
+                }
+                None => self.status = Some("The effect chain is empty".into()),
+            },
+            Action::InsertTone => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::InsertTone,
+                    input: String::new(),
+                };
+            }
+            Action::GoToSample => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::GoToSample,
+                    input: String::new(),
+                };
+            }
+            Action::LoadReference => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::Reference,
+                    input: String::new(),
+                };
+            }
+            Action::SelectBetweenMarkers => {
+                let idx = self.markers.partition_point(|m| m.pos <= self.cursor);
+                let start = match idx.checked_sub(1) {
+                    Some(i) => self.markers[i].pos,
+                    None => Duration::ZERO,
+                };
+                let end = match self.markers.get(idx) {
+                    Some(m) => m.pos,
+                    None => self.duration(),
+                };
+                log::debug!("Selecting between markers ({start:?}, {end:?})");
+                self.jump_cursor_to(end);
+                self.mode = Mode::Select(Selection { start, end });
+            }
+            Action::SelectSegment => {
+                let Some((start, end)) = self
+                    .segments()
+                    .into_iter()
+                    .find(|&(start, end)| start <= self.cursor && self.cursor < end)
+                else {
+                    self.status = Some("No sound at the cursor".into());
+                    return Ok(());
+                };
+                log::debug!("Selecting segment ({start:?}, {end:?})");
+                self.jump_cursor_to(end);
+                self.mode = Mode::Select(Selection { start, end });
+            }
+            Action::NextSegment | Action::PrevSegment => {
+                let segments = self.segments();
+                let start = if matches!(action, Action::NextSegment) {
+                    segments.iter().find(|s| s.0 > self.cursor)
+                } else {
+                    segments.iter().rev().find(|s| s.0 < self.cursor)
+                };
+                match start {
+                    Some(&(start, _)) => self.jump_cursor_to(start),
+                    None => log::info!("No segment to move to"),
+                }
+            }
+            Action::SelectToNextMarker | Action::SelectToPrevMarker => {
+                let marker = if matches!(action, Action::SelectToNextMarker) {
+                    self.markers.iter().find(|m| m.pos > self.cursor)
+                } else {
+                    self.markers.iter().rev().find(|m| m.pos < self.cursor)
+                };
+                let Some(pos) = marker.map(|m| m.pos) else {
+                    log::info!("No marker to select to");
+                    return Ok(());
+                };
+                match self.mode {
+                    // extend the current selection
+                    Mode::Select(_) => {}
+                    Mode::Normal => self.mode = Mode::Select(Selection::new(self.cursor)),
+                    Mode::Effect { .. }
+                    | Mode::Prompt { .. }
+                    | Mode::Confirm(_)
+                    | Mode::Pick(_) => {
+                        return Ok(());
+                    }
+                }
+                log::debug!("Selecting from {:?} to marker at {pos:?}", self.cursor);
+                self.jump_cursor_to(pos);
+            }
+            Action::Downmix => {
+                if self.source.channels() == 1 {
+                    log::info!("Already mono");
+                } else if self.confirm_destructive {
+                    self.mode = Mode::Confirm(Confirm::Downmix);
+                } else {
+                    self.busy("Downmixing", App::downmix);
+                }
+            }
+            Action::Upmix => {
+                if self.source.channels() != 1 {
+                    log::info!("Can only upmix mono to stereo");
+                    return Ok(());
+                }
+                log::debug!("Upmixing mono to stereo");
+                self.set_source(Audio::from(&self.source).upmix().into());
+            }
+            Action::NormalizeFile if self.confirm_destructive => {
+                self.mode = Mode::Confirm(Confirm::Normalize);
+            }
+            Action::NormalizeFile => self.busy("Normalizing", App::normalize_file),
+            Action::Undo => self.restore(Direction::Undo),
+            Action::Redo => self.restore(Direction::Redo),
+            Action::ToggleMonoMonitor => {
+                self.mono_monitor = !self.mono_monitor;
+                log::debug!("Mono monitoring: {}", self.mono_monitor);
+            }
+            Action::TapTempo => self.tap_tempo(Instant::now()),
+            Action::MeasureInterval => self.measure_interval(),
+            Action::ToggleSampleAxis => {
+                self.sample_axis = !self.sample_axis;
+                log::debug!("Sample axis: {}", self.sample_axis);
+            }
+            Action::ToggleLogTime => {
+                self.log_time = !self.log_time;
+                log::debug!("Log time: {}", self.log_time);
+            }
+            Action::ToggleClick => {
+                if self.beat().is_none() {
+                    self.status = Some("Set a bpm to use the click".into());
+                    return Ok(());
+                }
+                self.click = !self.click;
+                log::debug!("Click: {}", self.click);
+            }
+            Action::ToggleLog => {
+                self.show_log = !self.show_log;
+                self.log_scroll = 0;
+            }
+            Action::CycleLogLevel => {
+                let level = logger::cycle_level();
+                self.status = Some(format!("Log level: {}", level.as_str().to_lowercase()));
+            }
+            Action::ToggleEffectCompare => {
+                self.effect_compare = !self.effect_compare;
+                log::debug!("Effect compare: {}", self.effect_compare);
+            }
+            Action::ToggleInstructions => {
+                self.show_instructions = !self.show_instructions;
+                log::debug!("Instructions: {}", self.show_instructions);
+            }
+            Action::ScrollLogUp => {
+                let max = logger::RECORDS.lines().len().saturating_sub(1);
+                self.log_scroll = (self.log_scroll + 1).min(max);
+            }
+            Action::ScrollLogDown => {
+                self.log_scroll = self.log_scroll.saturating_sub(1);
+            }
+            Action::MatchLoudness
+                if self.confirm_destructive
+                    && self.reference.is_some()
+                    && matches!(self.mode, Mode::Normal) =>
+            {
+                self.mode = Mode::Confirm(Confirm::MatchLoudness);
+            }
+            Action::MatchLoudness => self.match_loudness(),
+            Action::Bounce => self.bounce(),
+            Action::DuplicateAll => self.duplicate_all(),
+            Action::CycleMarkerCategory => {
+                let cursor = self.cursor;
+                let Some(marker) = self
+                    .markers
+                    .iter_mut()
+                    .min_by_key(|m| m.pos.abs_diff(cursor))
+                else {
+                    self.status = Some("No markers".into());
+                    return Ok(());
+                };
+                marker.category = marker.category.next();
+                log::debug!("Marked {:?} as {:?}", marker.pos, marker.category);
+                self.status = Some(format!(
+                    "Marker at {:.3}s is {}",
+                    marker.pos.as_secs_f64(),
+                    marker.category.name()
+                ));
+            }
+            Action::AddMarker => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::MarkerLabel { pos: self.cursor },
+                    input: String::new(),
+                };
+            }
+            Action::SearchMarker => {
+                self.mode = Mode::Prompt {
+                    prompt: Prompt::SearchMarker {
+                        origin: self.cursor,
+                    },
+                    input: String::new(),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // Converts the position reported by the sink to the one to display.
+    fn play_position(&mut self, reported: Duration, now: Instant) -> Duration {
+        if !self.smooth_playhead {
+            return reported;
+        }
+        self.play_clock
+            .get_or_insert_with(|| PlayClock::new(reported, now))
+            .position(reported, now)
+    }
+
+    // Moves the cursor back from the end of the file according to play_at_end, returning
+    // whether there is now anything to play.
+    fn rewind_for_play(&mut self) -> bool {
+        let pos = match self.play_at_end {
+            PlayAtEnd::Nothing => return false,
+            PlayAtEnd::Rewind => Duration::ZERO,
+            PlayAtEnd::Last => self.duration().saturating_sub(self.play_at_end_length),
+        };
+        log::debug!("At the end, playing from {pos:?}");
+        self.jump_cursor_to(pos);
+        self.cursor < self.duration()
+    }
+
+    // Called when the sink runs out of audio while playing.
+    fn finish_playback(&mut self) {
+        if self.loop_all && !self.duration().is_zero() {
+            log::debug!("Looping back to the start");
+            self.sink
+                .append(self.to_output(self.playback_source(Duration::ZERO)));
+            // the sink's position starts over with each source
+            self.looping = Some((Duration::ZERO, self.duration()));
+            self.play_clock = None;
+            return;
+        }
+        log::debug!("Done playing");
+        self.playing = false;
+        self.play_clock = None;
+        self.needs_redraw = true;
+        if let Some(trail) = &mut self.trail {
+            trail.clear();
+        }
+    }
+
+    // Returns how long to wait for input before there is something else to do, or None to
+    // wait indefinitely.
+    fn poll_timeout(&self, now: Instant) -> Option<Duration> {
+        let mut timeout = None;
+        if self.playing {
+            timeout = Some(self.poll_interval);
+        }
+        // a draw was held back by max_fps, so wake up in time to make it
+        if self.needs_redraw {
+            let wait = self.last_draw.map_or(Duration::ZERO, |last| {
+                (last + self.frame_interval).saturating_duration_since(now)
+            });
+            timeout = Some(timeout.map_or(wait, |t: Duration| t.min(wait)));
+        }
+        // wake up for the next step of a macro
+        if let Some(&(at, _)) = self.scheduled.first() {
+            let wait = at.saturating_duration_since(now);
+            timeout = Some(timeout.map_or(wait, |t: Duration| t.min(wait)));
+        }
+        timeout
+    }
+
+    fn handle_events(&mut self) -> Result<()> {
+        self.run_scheduled(Instant::now())?;
+        if self.playing {
+            let pos = self.play_position(self.sink.get_pos(), Instant::now());
+            let playhead = self.playhead;
+            self.playhead = match self.looping {
+                Some((start, len)) if !len.is_zero() => {
+                    let pos = pos.as_nanos() % len.as_nanos();
+                    start + Duration::from_nanos(pos as u64)
+                }
+                _ => self.cursor + pos,
+            };
+            if self.playhead != playhead {
+                self.needs_redraw = true;
+                if let Some(trail) = &mut self.trail {
+                    trail.push_front(playhead);
+                    trail.truncate(TRAIL_COLORS.len());
+                }
+            }
+            if self.sink.empty() {
+                self.finish_playback();
+            }
+        }
+        if let Some(timeout) = self.poll_timeout(Instant::now()) {
+            if !event::poll(timeout)? {
+                return Ok(());
+            }
+        }
+        self.handle_event(event::read()?)
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        match event {
+            // it's important to check that the event is a key press event as
+            // crossterm also emits key release and repeat events on Windows.
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event)?;
+                self.needs_redraw = true;
+            }
+            Event::Paste(text) => {
+                self.handle_paste(text);
+                self.needs_redraw = true;
+            }
+            // returning lets the run loop redraw at the new size straight away
+            Event::Resize(width, height) => {
+                log::debug!("Resized to {width}x{height}");
+                self.wave_cache.take();
+                self.needs_redraw = true;
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        self.status = None;
+        if let Mode::Prompt { input, .. } = &mut self.mode {
+            match key.code {
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.update_prompt();
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.update_prompt();
+                }
+                KeyCode::Esc => self.cancel_prompt(),
+                KeyCode::Enter => self.submit_prompt()?,
+                _ => {}
+            }
+            return Ok(());
+        }
+        if let Mode::Pick(picker) = &mut self.mode {
+            match key.code {
+                KeyCode::Char(c) => picker.push(c),
+                KeyCode::Backspace => picker.pop(),
+                KeyCode::Up => picker.up(),
+                KeyCode::Down => picker.down(),
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Enter => match picker.enter() {
+                    Ok(Some(path)) => {
+                        self.mode = Mode::Normal;
+                        self.open_file(path);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        log::error!("Failed to list files: {err:?}");
+                        self.status = Some(format!("Cannot list files: {err}"));
+                    }
+                },
+                _ => {}
+            }
+            return Ok(());
+        }
+        if let Mode::Confirm(confirm) = &self.mode {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y') => self.accept_confirm(),
+                KeyCode::Char('n') if matches!(confirm, Confirm::Quit) => {
+                    log::info!("Exiting without saving");
+                    self.exit = true;
+                }
+                KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('c') => self.cancel_confirm(),
+                _ => {}
+            }
+            return Ok(());
+        }
+        let Some(binding) = self.binds.apply(key) else {
+            log::trace!("Mapped key to no action");
+            return Ok(());
+        };
+        log::trace!("Mapped key to {binding:?}");
+        match binding {
+            Binding::Action(actions) => {
+                for action in actions.clone() {
+                    self.apply_action(action)?;
+                }
+            }
+            Binding::Macro(steps) => {
+                let steps = steps.clone();
+                self.start_macro(&steps, Instant::now())?;
+            }
+            Binding::Chain(_) => {}
+        }
+        Ok(())
+    }
+
+    // Queues the steps of a macro and runs those that are due straight away.
+    fn start_macro(&mut self, steps: &[(Action, Option<Duration>)], now: Instant) -> Result<()> {
+        self.schedule(steps, now);
+        self.run_scheduled(now)
+    }
+
+    // Queues the steps of a macro to run from now on.
+    fn schedule(&mut self, steps: &[(Action, Option<Duration>)], now: Instant) {
+        let mut at = now;
+        for &(action, wait) in steps {
+            let idx = self.scheduled.partition_point(|&(t, _)| t <= at);
+            self.scheduled.insert(idx, (at, action));
+            at += wait.unwrap_or_default();
+        }
+    }
+
+    // Applies the scheduled actions that are due by now, in order.
+    fn run_scheduled(&mut self, now: Instant) -> Result<()> {
+        while let Some(&(at, action)) = self.scheduled.first() {
+            if at > now {
+                break;
+            }
+            self.scheduled.remove(0);
+            log::debug!("Running scheduled {action:?}");
+            self.apply_action(action)?;
+            self.needs_redraw = true;
+        }
+        Ok(())
+    }
+
+    // Returns the waveform points for the visible window, reusing the last result if the
+    // window and width haven't changed.
+    fn visible_wave_points(&self, width: u16) -> Vec<(f64, f64)> {
+        // log-time points depend on the cursor, so aren't worth caching
+        if self.log_time {
+            return self.wave_points(self.window_start, self.window_end, width, None);
+        }
+        let window = (self.window_start, self.window_end);
+        if let Some(cache) = &*self.wave_cache.borrow() {
+            if cache.window == window && cache.width == width {
+                return cache.points.clone();
+            }
+        }
+        let points = self.wave_points(self.window_start, self.window_end, width, None);
+        *self.wave_cache.borrow_mut() = Some(WaveCache {
+            window,
+            width,
+            points: points.clone(),
+        });
+        points
+    }
+
+    // Returns (seconds, amplitude) points for the visible audio between start and end.
+    // Each braille cell is two dots wide, so the window is split into two sub-columns per
+    // column and only the minimum and maximum of each are kept, in the order they occur.
+    // Drawing a line between them fills the sub-column without aliasing.
+    fn wave_points(
+        &self,
+        start: Duration,
+        end: Duration,
+        width: u16,
+        effect: Option<&Effect>,
+    ) -> Vec<(f64, f64)> {
+        self.source_points(&self.source, start, end, width, effect)
+    }
+
+    // Returns the peak level of each of width equal columns spanning the whole file, in percent.
+    fn overview(&self, width: u16) -> Vec<u64> {
+        if let Some((cached_width, peaks)) = &*self.overview_cache.borrow() {
+            if *cached_width == width {
+                return peaks.clone();
+            }
+        }
+        let peaks = self.compute_overview(width);
+        *self.overview_cache.borrow_mut() = Some((width, peaks.clone()));
+        peaks
+    }
+
+    fn compute_overview(&self, width: u16) -> Vec<u64> {
+        let audio = Audio::from(&self.source);
+        let channels = audio.channels as usize;
+        let frames = audio.frames();
+        (0..width as usize)
+            .map(|col| {
+                let start = col * frames / width as usize;
+                let end = ((col + 1) * frames / width as usize)
+                    .max(start + 1)
+                    .min(frames);
+                let samples = audio.samples.get(start * channels..end * channels);
+                let peak = samples
+                    .unwrap_or_default()
+                    .iter()
+                    .fold(0.0f32, |max, v| max.max(v.abs()));
+                (peak.min(1.0) * 100.0).round() as u64
+            })
+            .collect()
+    }
+
+    // Draws the whole file's envelope faintly, boxing the part in view.
+    fn render_minimap(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered().title("overview");
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let area = inner;
+        let data = self.overview(area.width);
+        let wave_area = Rect {
+            y: area.y + 1,
+            height: area.height.saturating_sub(2),
+            ..area
+        };
+        Sparkline::default()
+            .data(&data)
+            .max(100)
+            .style(Style::new().dark_gray())
+            .render(wave_area, buf);
+
+        let duration = self.duration().as_secs_f64();
+        // the view box needs room for both of its sides
+        if duration == 0.0 || area.width < 2 {
+            return;
+        }
+        let col = |pos: Duration| (pos.as_secs_f64() / duration * area.width as f64) as u16;
+        let start = col(self.window_start).min(area.width - 2);
+        let end = col(self.window_end).min(area.width).max(start + 2);
+        let view = Rect {
+            x: area.x + start,
+            width: end - start,
+            ..area
+        };
+        Block::bordered().yellow().render(view, buf);
+    }
+
+    // Returns where a time in seconds is drawn on the x-axis. In log-time view, distance from
+    // the cursor is compressed logarithmically, magnifying the audio around the cursor while
+    // keeping the edges of the window in place.
+    fn time_x(&self, secs: f64) -> f64 {
+        if !self.log_time {
+            return secs;
+        }
+        let (start, end) = (
+            self.window_start.as_secs_f64(),
+            self.window_end.as_secs_f64(),
+        );
+        let cursor = self.cursor.as_secs_f64().clamp(start, end);
+        let offset = secs - cursor;
+        let span = if offset < 0.0 {
+            cursor - start
+        } else {
+            end - cursor
+        };
+        if span <= 0.0 {
+            return secs;
+        }
+        let scale = (1.0 + LOG_TIME_ZOOM * offset.abs() / span).ln() / (1.0 + LOG_TIME_ZOOM).ln();
+        cursor + offset.signum() * span * scale
+    }
+
+    // Like wave_points, but for any source sharing the time axis.
+    fn source_points(
+        &self,
+        source: &SamplesBuffer<f32>,
+        start: Duration,
+        end: Duration,
+        width: u16,
+        effect: Option<&Effect>,
+    ) -> Vec<(f64, f64)> {
+        let start = start.max(self.window_start);
+        let end = end.min(self.window_end);
+        if end <= start || width == 0 {
+            return vec![];
+        }
+
+        let mut samples: Vec<_> = source
+            .clone()
+            .skip_duration(start)
+            .take_duration(end - start)
+            .collect();
+        if let Some(effect) = effect {
+            effect.apply(&mut samples, source.channels(), source.sample_rate());
+        }
+
+        // samples are interleaved, so each frame spans one sample per channel
+        let sample_rate = source.sample_rate() as f64 * source.channels() as f64;
+        let window_start = self.window_start.as_secs_f64();
+        let window_secs = (self.window_end - self.window_start).as_secs_f64();
+        let cols = width as u32 * 2;
+        let mut points: Vec<Point> = vec![];
+        // (sub-column, min, max) for the sub-column being filled
+        let mut current: Option<(u32, Point, Point)> = None;
+        let flush = |points: &mut Vec<Point>, (_, min, max): (u32, Point, Point)| {
+            if min == max {
+                points.push(min);
+            } else if min.0 <= max.0 {
+                points.extend([min, max]);
+            } else {
+                points.extend([max, min]);
+            }
+        };
+        for (i, v) in samples.into_iter().enumerate() {
+            let point = (
+                self.time_x((i as f64 / sample_rate) + start.as_secs_f64()),
+                v as f64,
+            );
+            let col = ((point.0 - window_start) / window_secs * cols as f64) as u32;
+            let col = col.min(cols - 1);
+            match &mut current {
+                Some((c, min, max)) if *c == col => {
+                    if point.1 < min.1 {
+                        *min = point;
+                    }
+                    if point.1 > max.1 {
+                        *max = point;
+                    }
+                }
+                _ => {
+                    if let Some(done) = current.replace((col, point, point)) {
+                        flush(&mut points, done);
+                    }
+                }
+            }
+        }
+        if let Some(done) = current {
+            flush(&mut points, done);
+        }
+        points
+    }
+}
 
 impl Widget for &App {
     fn render(self, area: ratatui::prelude::Rect, buf: &mut Buffer) {
-        let title = Title::from("atuio".bold());
-        let instructions = Title::from(ratatui::text::Line::from(vec![
-            " Move ".into(),
-            "<WASD>".blue().bold(),
-            " Rect ".into(),
-            "<R>".blue().bold(),
-            " Quit ".into(),
-            "<Q> ".blue().bold(),
-        ]));
+        let mut title = vec!["atuio".bold()];
+        if self.mono_monitor {
+            title.push(" MONO".yellow().bold());
+        }
+        if self.click {
+            title.push(" CLICK".yellow().bold());
+        }
+        if self.log_time {
+            title.push(" LOG".yellow().bold());
+        }
+        if self.recorder.is_some() {
+            title.push(" REC".red().bold());
+        }
+        if let Mode::Effect { effect, .. } = &self.mode {
+            title.push(format!(" {}", effect.describe()).green());
+            if effect.pinned() {
+                title.push(" (limit)".yellow());
+            }
+        }
+        let title = Title::from(Line::from(title));
+        let instructions = if self.show_instructions {
+            Title::from(ratatui::text::Line::from(vec![
+                " Move ".into(),
+                "<WASD>".blue().bold(),
+                " Rect ".into(),
+                "<R>".blue().bold(),
+                " Quit ".into(),
+                "<Q> ".blue().bold(),
+            ]))
+        } else {
+            Title::from(ratatui::text::Line::from(vec![
+                " ".into(),
+                "<H> ".blue().bold(),
+            ]))
+        };
         let block = Block::bordered()
             .title(title.alignment(Alignment::Center))
             .title(
@@ -331,288 +2803,3440 @@ impl Widget for &App {
                     .alignment(Alignment::Center)
                     .position(ratatui::widgets::block::Position::Bottom),
             )
-            .border_set(ratatui::symbols::border::THICK);
-        block.render(area, buf);
+            .border_set(ratatui::symbols::border::THICK);
+        block.render(area, buf);
+
+        let prompt = match &self.mode {
+            Mode::Prompt { prompt, input } => Some(Line::from(vec![
+                prompt.label().bold(),
+                input.as_str().into(),
+            ])),
+            Mode::Confirm(confirm) => Some(Line::from(confirm.label().bold())),
+            Mode::Pick(picker) => Some(Line::from(vec![
+                "open: ".bold(),
+                picker.filter.as_str().into(),
+            ])),
+            _ => self
+                .status
+                .clone()
+                .or_else(|| self.selection_readout())
+                .or_else(|| self.sample_readout())
+                .map(Line::from),
+        };
+        let area = match prompt {
+            Some(line) => {
+                let [area, prompt_area] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+                line.render(prompt_area, buf);
+                area
+            }
+            None => area,
+        };
+
+        let area = if self.show_log {
+            let [area, log_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(8)]).areas(area);
+            let lines = logger::RECORDS.lines();
+            let end = lines.len().saturating_sub(self.log_scroll);
+            let start = end.saturating_sub(log_area.height.saturating_sub(2) as usize);
+            let list = List::new(lines[start..end].iter().map(String::as_str)).block(
+                Block::bordered().title(format!(
+                    "log ({})",
+                    logger::LEVEL.get().as_str().to_lowercase()
+                )),
+            );
+            Widget::render(list, log_area, buf);
+            area
+        } else {
+            area
+        };
+
+        let area = if let Mode::Pick(picker) = &self.mode {
+            let entries = picker.visible();
+            let height = (entries.len() as u16 + 2).min(area.height / 2).max(1);
+            let [area, picker_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(height)]).areas(area);
+            // scroll to keep the selected entry in view
+            let rows = height.saturating_sub(2) as usize;
+            let skip = (picker.selected + 1).saturating_sub(rows);
+            let list = List::new(entries.into_iter().enumerate().skip(skip).map(|(i, name)| {
+                if i == picker.selected {
+                    Line::from(name.reversed())
+                } else {
+                    Line::from(name)
+                }
+            }))
+            .block(Block::bordered().title(picker.dir.display().to_string()));
+            Widget::render(list, picker_area, buf);
+            area
+        } else {
+            area
+        };
+
+        let area = if self.effect_chain.is_empty() {
+            area
+        } else {
+            let height = self.effect_chain.len() as u16 + 2;
+            let [area, chain_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(height)]).areas(area);
+            let list = List::new(self.effect_chain.iter().map(Effect::describe))
+                .block(Block::bordered().title("effect chain"));
+            Widget::render(list, chain_area, buf);
+            area
+        };
+
+        // when zoomed in, show where the view is within the whole file
+        let zoomed = !self.window_start.is_zero() || self.window_end < self.duration();
+        let area = if zoomed {
+            let [area, minimap_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(MINIMAP_HEIGHT)])
+                    .areas(area);
+            self.render_minimap(minimap_area, buf);
+            area
+        } else {
+            area
+        };
+
+        let (min_amp, max_amp) = (-1.0, 1.0);
+        let start_secs = self.window_start.as_secs_f64();
+        let end_secs = self.window_end.as_secs_f64();
+
+        // positions are placed on the x-axis in seconds, or frames if sample_axis is set
+        let x_scale = if self.sample_axis {
+            self.source.sample_rate() as f64
+        } else {
+            1.0
+        };
+        let x = |pos: Duration| self.time_x(pos.as_secs_f64()) * x_scale;
+        let scale_points = |points: Vec<Point>| -> Vec<Point> {
+            points.into_iter().map(|(t, v)| (t * x_scale, v)).collect()
+        };
+        let x_labels = if self.sample_axis {
+            [x(self.window_start), x(self.window_end)].map(|f| format!("{f:.0}"))
+        } else {
+            [format!("{start_secs}s"), format!("{end_secs}s")]
+        };
+        // labels are spaced evenly from the bottom to the top
+        let y_labels = amplitude_labels(min_amp, max_amp, self.y_ticks);
+
+        // This mirrors how ratatui places the graph to the right of the y-axis labels.
+        let label_width = y_labels
+            .iter()
+            .map(String::len)
+            .chain([x_labels[0].len().saturating_sub(1)])
+            .max()
+            .unwrap_or_default() as u16;
+        let graph_x = area.x + label_width.min(area.width / 3) + 1;
+        let graph_width = area.right().saturating_sub(graph_x);
+        // the first column holds the y-axis line
+        let plot_width = graph_width.saturating_sub(1);
+
+        let wave_data = scale_points(self.visible_wave_points(plot_width));
+        // Outside of a selection the wave is dimmed, so the selected part stands out.
+        let focus = match &self.mode {
+            Mode::Select(sel) => Some(sel.normalize()),
+            Mode::Effect { selection, .. } => Some(selection.normalize()),
+            Mode::Normal | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => None,
+        };
+        let (wave_data, wave_after_data) = match focus {
+            Some((start, end)) => {
+                let (before, rest): (Vec<_>, Vec<_>) =
+                    wave_data.into_iter().partition(|p| p.0 < x(start));
+                (before, rest.into_iter().filter(|p| p.0 > x(end)).collect())
+            }
+            None => (wave_data, vec![]),
+        };
+        let wave_style = match focus {
+            // the chart only keeps the color of each cell, so there's no dim modifier
+            Some(_) => Style::default().dark_gray(),
+            None => Style::default().cyan(),
+        };
+        let reference_data = match &self.reference {
+            Some(reference) => scale_points(self.source_points(
+                reference,
+                self.window_start,
+                self.window_end,
+                plot_width,
+                None,
+            )),
+            None => vec![],
+        };
+
+        let selected_data = scale_points(match &self.mode {
+            Mode::Select(sel) => {
+                let (start, end) = sel.normalize();
+                self.wave_points(start, end, plot_width, None)
+            }
+            Mode::Effect {
+                selection, effect, ..
+            } => {
+                let (start, end) = selection.normalize();
+                self.wave_points(start, end, plot_width, Some(effect))
+            }
+            Mode::Normal | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => vec![],
+        });
+
+        let compare_data = match &self.mode {
+            Mode::Effect { selection, .. } if self.effect_compare => {
+                let (start, end) = selection.normalize();
+                scale_points(self.wave_points(start, end, plot_width, None))
+            }
+            _ => vec![],
+        };
+
+        let beat_data: Vec<_> = match self.beat() {
+            // skip gridlines that would be too dense to make out
+            Some(beat) if (self.window_end - self.window_start) / 2 < beat * area.width as u32 => {
+                let first = (start_secs / beat.as_secs_f64()).ceil() as u32;
+                (first..)
+                    .map(|i| beat * i)
+                    .take_while(|t| *t <= self.window_end)
+                    .map(|t| [(x(t), min_amp), (x(t), max_amp)])
+                    .collect()
+            }
+            _ => vec![],
+        };
+
+        let cursor_data = [(x(self.cursor), min_amp), (x(self.cursor), max_amp)];
+        let mut datasets: Vec<_> = beat_data
+            .iter()
+            .map(|data| {
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().dark_gray())
+                    .data(data)
+            })
+            .collect();
+        datasets.extend([
+            // reference, behind the wave
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().dark_gray())
+                .data(reference_data.as_slice()),
+            // wave
+            Dataset::default()
+                .name(self.path.file_name().and_then(|f| f.to_str()).unwrap_or(""))
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(wave_style)
+                .data(wave_data.as_slice()),
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(wave_style)
+                .data(wave_after_data.as_slice()),
+            // the selection before the effect, showing where the preview differs
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().gray())
+                .data(compare_data.as_slice()),
+            // selected
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().green())
+                .data(selected_data.as_slice()),
+            // cursor
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().white())
+                .data(&cursor_data),
+        ]);
+
+        let selection_data = match self.mode {
+            Mode::Select(Selection { start, end })
+            | Mode::Effect {
+                selection: Selection { start, end },
+                ..
+            } => (
+                [(x(start), min_amp), (x(start), max_amp)],
+                [(x(end), min_amp), (x(end), max_amp)],
+            ),
+            Mode::Normal | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {
+                ([(0.0, 0.0); 2], [(0.0, 0.0); 2])
+            }
+        };
+
+        match self.mode {
+            Mode::Select(_) | Mode::Effect { .. } => {
+                datasets.push(
+                    Dataset::default()
+                        .marker(symbols::Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().green())
+                        .data(&selection_data.0),
+                );
+                datasets.push(
+                    Dataset::default()
+                        .marker(symbols::Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().green())
+                        .data(&selection_data.1),
+                )
+            }
+            Mode::Normal | Mode::Prompt { .. } | Mode::Confirm(_) | Mode::Pick(_) => {}
+        }
+
+        let marker_data: Vec<_> = self
+            .markers
+            .iter()
+            .filter(|m| m.pos >= self.window_start && m.pos <= self.window_end)
+            .map(|m| ([(x(m.pos), min_amp), (x(m.pos), max_amp)], m.category))
+            .collect();
+        for (data, category) in &marker_data {
+            datasets.push(
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(category.color()))
+                    .data(data),
+            );
+        }
+
+        let trail_data: Vec<_> = match &self.trail {
+            Some(trail) if self.playing => trail
+                .iter()
+                .map(|&pos| [(x(pos), min_amp), (x(pos), max_amp)])
+                .collect(),
+            _ => vec![],
+        };
+        // oldest first, so newer positions are drawn over older ones
+        for (data, color) in trail_data.iter().zip(TRAIL_COLORS).rev() {
+            datasets.push(
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(color))
+                    .data(data),
+            );
+        }
+
+        let playhead_data = [(x(self.playhead), min_amp), (x(self.playhead), max_amp)];
+        if self.playing {
+            datasets.push(
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().red())
+                    .data(&playhead_data),
+            )
+        }
+
+        let x_axis = Axis::default()
+            .style(Style::default().white())
+            .bounds([x(self.window_start), x(self.window_end)])
+            .labels(x_labels.clone());
+
+        let y_axis = Axis::default()
+            .style(Style::default().white())
+            .bounds([min_amp, max_amp])
+            .labels(y_labels.clone());
+
+        let chart = Chart::new(datasets).x_axis(x_axis).y_axis(y_axis);
+
+        chart.render(area, buf);
+
+        // Chart can't annotate points, so write marker labels over the graph ourselves.
+        let window_secs = end_secs - start_secs;
+        for marker in &self.markers {
+            let Some(label) = &marker.label else {
+                continue;
+            };
+            if marker.pos < self.window_start || marker.pos > self.window_end {
+                continue;
+            }
+            let offset = (self.time_x(marker.pos.as_secs_f64()) - start_secs) / window_secs;
+            let x = graph_x + (offset * graph_width.saturating_sub(1) as f64).round() as u16 + 1;
+            if x < area.right() && area.height > 1 {
+                buf.set_stringn(
+                    x,
+                    area.y + 1,
+                    label,
+                    (area.right() - x) as usize,
+                    Style::default().fg(marker.category.color()),
+                );
+            }
+        }
+    }
+}
+
+// Returns the path in text pasted by a terminal when a file is dropped onto it, which may be
+// quoted, have escaped spaces or be a file:// URL.
+fn dropped_path(text: &str) -> std::path::PathBuf {
+    let text = text.trim();
+    let text = ['\'', '"']
+        .iter()
+        .find_map(|&q| text.strip_prefix(q).and_then(|t| t.strip_suffix(q)))
+        .unwrap_or(text);
+    let text = text.strip_prefix("file://").unwrap_or(text);
+    text.replace("\\ ", " ").into()
+}
+
+pub fn start(config: Config, path: std::path::PathBuf) -> Result<()> {
+    // Load before taking over the terminal so decode errors are printed to a usable shell.
+    let mut app = App::new(config, path)?;
+
+    // This also installs a panic hook that restores the terminal before the panic is printed.
+    let mut terminal = ratatui::init();
+    // Don't return early from here on, or the terminal is left in raw mode.
+    let app_result = match terminal
+        .clear()
+        .and_then(|()| crossterm::execute!(std::io::stdout(), EnableBracketedPaste))
+    {
+        Ok(()) => app.run(terminal),
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = crossterm::execute!(std::io::stdout(), DisableBracketedPaste) {
+        log::error!("Failed to disable bracketed paste: {err}");
+    }
+    ratatui::restore();
+    app_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    struct Test {
+        app: App,
+    }
+
+    impl Test {
+        fn load(path: &str) -> Test {
+            Self::load_with_config(path, Config::default())
+        }
+
+        fn load_with_config(path: &str, config: Config) -> Test {
+            Self::open(std::path::Path::new("testdata").join(path), config)
+        }
+
+        fn open(path: std::path::PathBuf, config: Config) -> Test {
+            let mut app = App::new(config, path).unwrap();
+            // the output depends on the machine, so play audio as is
+            app.output_rate = None;
+            Test { app }
+        }
+
+        fn render(&self) -> String {
+            let mut buf = Buffer::empty(layout::Rect::new(0, 0, 160, 20));
+            self.app.render(buf.area, &mut buf);
+            buf_string(&buf)
+        }
+
+        // Renders the color of each braille cell as a letter, e.g. c for cyan.
+        fn render_styles(&self) -> String {
+            let mut buf = Buffer::empty(layout::Rect::new(0, 0, 160, 20));
+            self.app.render(buf.area, &mut buf);
+            buf.content
+                .chunks(buf.area.width as usize)
+                .map(|line| {
+                    line.iter()
+                        .map(|cell| match cell.symbol().chars().next() {
+                            Some('\u{2801}'..='\u{28ff}') => match cell.fg {
+                                Color::Green => 'g',
+                                Color::DarkGray => 'd',
+                                Color::Cyan => 'c',
+                                Color::Gray => 'o',
+                                Color::Red => 'r',
+                                Color::Indexed(160) => '1',
+                                Color::Indexed(124) => '2',
+                                Color::Indexed(88) => '3',
+                                Color::Yellow => 'y',
+                                Color::LightGreen => 'G',
+                                Color::LightRed => 'R',
+                                Color::Magenta => 'M',
+                                _ => '?',
+                            },
+                            _ => ' ',
+                        })
+                        .collect::<String>()
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        fn input(&mut self, keys: &str) {
+            let chars: Vec<_> = keys.chars().collect();
+            input(&mut self.app, chars.as_slice());
+        }
+    }
+
+    fn buf_string(buf: &Buffer) -> String {
+        buf.content
+            .chunks(buf.area.width as usize)
+            .map(|line| {
+                line.iter()
+                    .map(|cell| cell.symbol().to_string())
+                    .collect::<Vec<_>>()
+                    .join("")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn input(app: &mut App, keys: &[char]) {
+        for c in keys {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(*c),
+            };
+            app.handle_event(Event::Key(code.into())).unwrap();
+            // as the run loop would after drawing
+            app.run_busy();
+        }
+    }
+
+    // Returns the frequency with the most energy, in steps of 10hz.
+    fn dominant_frequency(samples: &[f32], sample_rate: u32) -> f32 {
+        let n = samples.len() as f32;
+        (1..=200)
+            .map(|bin| bin as f32 * 10.0)
+            .map(|freq| {
+                let (re, im) = samples
+                    .iter()
+                    .enumerate()
+                    .fold((0.0, 0.0), |(re, im), (i, v)| {
+                        let phase =
+                            2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32;
+                        (re + v * phase.cos(), im - v * phase.sin())
+                    });
+                (freq, (re * re + im * im) / n)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_tui_render_empty() {
+        let test = Test::load("sine440fade.wav");
+        assert_snapshot!("load", test.render());
+    }
+
+    #[test]
+    fn test_tui_move_cursor() {
+        let mut test = Test::load("sine440fade.wav");
+
+        test.input("llll");
+        assert_snapshot!("cursor_right", test.render());
+
+        test.input("hh");
+        assert_snapshot!("cursor_left", test.render());
+
+        test.input("gl");
+        assert_snapshot!("cursor_end", test.render());
+
+        test.input("gs");
+        assert_snapshot!("cursor_start", test.render());
+    }
+
+    #[test]
+    fn test_tui_zoom() {
+        let mut test = Test::load("sine440fade.wav");
+
+        let zoom0 = test.render();
+        assert_snapshot!("zoom0", zoom0);
+
+        test.input("z");
+        let zoom1 = test.render();
+        assert_snapshot!("zoom1", zoom1);
+
+        test.input("z");
+        let zoom2 = test.render();
+        assert_snapshot!("zoom2", zoom2);
+
+        test.input(&"z".repeat(8));
+        let zoom10 = test.render();
+        assert_snapshot!("zoom10", zoom10);
+
+        // scroll past the right bound to scroll the view
+        test.input(&"l".repeat(6));
+        assert_snapshot!("zoom10right", test.render());
+
+        // should scroll back to where we were
+        test.input(&"h".repeat(6));
+        assert_eq!(zoom10, test.render());
+
+        test.input(&"Z".repeat(8));
+        assert_eq!(zoom2, test.render());
+
+        test.input("Z");
+        assert_eq!(zoom1, test.render());
+
+        test.input("Z");
+        assert_eq!(zoom0, test.render());
+    }
+
+    #[test]
+    fn test_tui_select() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llllvlll");
+        assert_snapshot!("select_forward", test.render());
+        test.input("hhhhhh");
+        assert_snapshot!("select_backward", test.render());
+    }
+
+    #[test]
+    fn test_tui_select_all() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("%");
+        assert_snapshot!("select_all", test.render());
+    }
+
+    #[test]
+    fn test_tui_amplify() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llllvlllaiii");
+        assert_snapshot!("amplify", test.render());
+        assert!(test.render().contains("atuio Amplify +2.3dB"));
+    }
+
+    #[test]
+    fn test_tui_cut() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llllvllllllllllllx");
+        assert_snapshot!("cut", test.render());
+    }
+
+    #[test]
+    fn test_tui_insert_tone() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = test.app.source.total_duration().unwrap();
+        test.input("llll");
+        let at = test.app.cursor;
+
+        test.input("t880 0.1\n");
+        assert_eq!(
+            test.app.source.total_duration().unwrap(),
+            before + Duration::from_millis(100)
+        );
+        assert_eq!(test.app.cursor, at + Duration::from_millis(100));
+
+        let inserted: Vec<_> = test
+            .app
+            .source
+            .clone()
+            .skip_duration(at)
+            .take_duration(Duration::from_millis(100))
+            .collect();
+        assert_eq!(dominant_frequency(&inserted, 48000), 880.0);
+    }
+
+    #[test]
+    fn test_tui_insert_silence() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llll");
+        let at = test.app.cursor;
+        test.input("t0 0.05\n");
+        assert!(test
+            .app
+            .source
+            .clone()
+            .skip_duration(at)
+            .take_duration(Duration::from_millis(50))
+            .all(|v| v == 0.0));
+    }
+
+    #[test]
+    fn test_tui_save_as() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.wav");
+        let mut test = Test::load("sine440fade.wav");
+        let expected = test.app.source.clone().collect::<Vec<_>>().len();
+
+        test.input(&format!("S{}\n", path.display()));
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.path, path);
+        assert_eq!(
+            hound::WavReader::open(&path).unwrap().len() as usize,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_tui_save_as_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("existing.wav");
+        std::fs::write(&path, "original").unwrap();
+        let mut test = Test::load("sine440fade.wav");
+
+        test.input(&format!("S{}\n", path.display()));
+        assert!(matches!(&test.app.mode, Mode::Confirm(Confirm::Overwrite(p)) if *p == path));
+
+        // cancelling returns to the prompt
+        test.input("n");
+        assert!(matches!(
+            &test.app.mode,
+            Mode::Prompt { prompt: Prompt::SaveAs, input } if *input == path.display().to_string()
+        ));
+        test.input("\x1b");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+
+        // confirming overwrites
+        test.input(&format!("S{}\ny", path.display()));
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert!(hound::WavReader::open(&path).is_ok());
+    }
+
+    #[test]
+    fn test_tui_add_marker() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llllmverse\n");
+        test.input("llllm\n");
+
+        let markers: Vec<_> = test
+            .app
+            .markers
+            .iter()
+            .map(|m| (m.pos, m.label.clone()))
+            .collect();
+        assert_eq!(
+            markers,
+            [
+                (Duration::from_millis(40), Some("verse".to_string())),
+                (Duration::from_millis(80), None),
+            ]
+        );
+        assert_snapshot!("markers", test.render());
+    }
+
+    #[test]
+    fn test_tui_search_marker() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("llmverse\n");
+        test.input("llllmchorus\n");
+        test.input("llllmverse 2\n");
+        test.input("gs");
+
+        // jumps as the query is typed
+        test.input("/cho");
+        assert_eq!(test.app.cursor, Duration::from_millis(60));
+        test.input("\n");
+        assert_eq!(test.app.cursor, Duration::from_millis(60));
+
+        // searches forward from the cursor
+        test.input("/VERSE\n");
+        assert_eq!(test.app.cursor, Duration::from_millis(100));
+
+        // wraps around
+        test.input("l/verse\n");
+        assert_eq!(test.app.cursor, Duration::from_millis(20));
+
+        // cancelling returns to where the search started
+        test.input("/chorus\x1b");
+        assert_eq!(test.app.cursor, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_tui_effect_step() {
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                effect_step: 0.5,
+                fine_effect_step: 0.05,
+                ..Default::default()
+            },
+        );
+        let amount = |test: &Test| match test.app.mode {
+            Mode::Effect {
+                effect: Effect::Amplify { amount },
+                ..
+            } => amount,
+            _ => panic!("Not in effect mode"),
+        };
+
+        test.input("lvlla");
+        assert_eq!(amount(&test), 1.0);
+        test.input("i");
+        assert_eq!(amount(&test), 1.5);
+        test.input("uu");
+        assert_eq!(amount(&test), 0.5);
+        test.input("I");
+        assert_eq!(amount(&test), 0.55);
+        test.input("UU");
+        assert_eq!(amount(&test), 0.45);
+    }
+
+    #[test]
+    fn test_tui_downmix() {
+        let mut test = Test::load("sine440fade.wav");
+        test.app.source = SamplesBuffer::new(2, 4, vec![1.0, 0.0, 0.5, 0.5, -1.0, 0.0, 0.2, 0.4]);
+
+        test.input("cmy");
+        assert_eq!(test.app.source.channels(), 1);
+        assert_eq!(test.app.source.sample_rate(), 4);
+        assert_eq!(
+            test.app.source.clone().collect::<Vec<_>>(),
+            [0.5, 0.5, -0.5, 0.3]
+        );
+
+        // already mono
+        test.input("cm");
+        assert_eq!(test.app.source.clone().count(), 4);
+    }
+
+    #[test]
+    fn test_tui_upmix() {
+        let mut test = Test::load("sine440fade.wav");
+        let duration = test.app.source.total_duration();
+        let samples: Vec<_> = test.app.source.clone().collect();
+
+        test.input("cs");
+        assert_eq!(test.app.source.channels(), 2);
+        assert_eq!(test.app.source.total_duration(), duration);
+        assert_eq!(
+            test.app.source.clone().collect::<Vec<_>>(),
+            samples.iter().flat_map(|&v| [v, v]).collect::<Vec<_>>()
+        );
+
+        // already stereo
+        test.input("cs");
+        assert_eq!(test.app.source.clone().count(), samples.len() * 2);
+    }
+
+    #[test]
+    fn test_tui_select_between_markers() {
+        let mut test = Test::load("sine440fade.wav");
+        let selection = |test: &Test| match &test.app.mode {
+            Mode::Select(sel) => sel.normalize(),
+            _ => panic!("Not selecting"),
+        };
+
+        test.input("llllm\n");
+        test.input("llllllllm\n");
+
+        test.input("gsllllllM");
+        assert_eq!(
+            selection(&test),
+            (Duration::from_millis(40), Duration::from_millis(120))
+        );
+
+        // no marker before the cursor
+        test.input("vgslM");
+        assert_eq!(
+            selection(&test),
+            (Duration::ZERO, Duration::from_millis(40))
+        );
+
+        // no marker after the cursor
+        test.input("vgs");
+        test.input(&"l".repeat(15));
+        test.input("M");
+        assert_eq!(
+            selection(&test),
+            (Duration::from_millis(120), Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_tui_wave_points_width() {
+        let mut test = Test::load("sine440fade.wav");
+        let (start, end) = (test.app.window_start, test.app.window_end);
+        // a min and max for each half of a braille cell
+        for width in [1, 10, 80, 160, 1000] {
+            let points = test.app.wave_points(start, end, width, None);
+            assert!(
+                points.len() <= width as usize * 4,
+                "{} > {width}",
+                points.len()
+            );
+        }
+
+        // a single column keeps the loudest samples
+        let peak = test.app.source.clone().fold(0.0f32, |a, b| a.max(b.abs()));
+        let points = test.app.wave_points(start, end, 1, None);
+        let loudest = points.iter().fold(0.0f64, |a, p| a.max(p.1.abs()));
+        assert_eq!(loudest, peak as f64);
+        assert!(points.iter().any(|p| p.1 < 0.0) && points.iter().any(|p| p.1 > 0.0));
+
+        // selections are bound by the same columns as the window
+        test.input("lllllvllllll");
+        let points = test.app.wave_points(
+            Duration::from_millis(50),
+            Duration::from_millis(110),
+            160,
+            None,
+        );
+        assert!(points.len() <= 20 * 4, "{}", points.len());
+
+        // zoomed in past one sample per column
+        test.input("vzzzzzzzzz");
+        let (start, end) = (test.app.window_start, test.app.window_end);
+        let points = test.app.wave_points(start, end, 160, None);
+        assert!(!points.is_empty() && points.len() <= 160 * 4);
+
+        // points stay in time order
+        assert!(points.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn test_tui_antialias() {
+        // zoomed so each braille cell spans a few samples of a 440hz tone
+        let mut test = Test::load("sine440.wav");
+        test.app.window_end = Duration::from_millis(10);
+        assert_snapshot!("antialias", test.render());
+    }
+
+    #[test]
+    fn test_tui_copy_paste() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = test.app.source.total_duration().unwrap();
+
+        // copy 40ms-80ms
+        test.input("llllvllllyp");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(
+            test.app.source.total_duration().unwrap(),
+            before + Duration::from_millis(40)
+        );
+        assert_eq!(test.app.cursor, Duration::from_millis(120));
+
+        let copied: Vec<_> = test
+            .app
+            .source
+            .clone()
+            .skip_duration(Duration::from_millis(40))
+            .take_duration(Duration::from_millis(40))
+            .collect();
+        let pasted: Vec<_> = test
+            .app
+            .source
+            .clone()
+            .skip_duration(Duration::from_millis(80))
+            .take_duration(Duration::from_millis(40))
+            .collect();
+        assert_eq!(copied, pasted);
+    }
+
+    #[test]
+    fn test_tui_replace_selection() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = test.app.source.total_duration().unwrap();
+
+        // copy 40ms-80ms, then replace 140ms-240ms with it
+        test.input("llllvlllly");
+        test.input("llllllvllllllllllr");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(
+            test.app.source.total_duration().unwrap(),
+            before - Duration::from_millis(60)
+        );
+        assert_eq!(test.app.cursor, Duration::from_millis(180));
+    }
+
+    #[test]
+    fn test_tui_extend_selection_beat() {
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                bpm: Some(600.0),
+                ..Default::default()
+            },
+        );
+        let selection = |test: &Test| match &test.app.mode {
+            Mode::Select(sel) => sel.normalize(),
+            _ => panic!("Not selecting"),
+        };
+
+        test.input("llllvb");
+        assert_eq!(
+            selection(&test),
+            (Duration::from_millis(40), Duration::from_millis(140))
+        );
+        test.input("b");
+        assert_eq!(
+            selection(&test),
+            (Duration::from_millis(40), Duration::from_millis(240))
+        );
+        assert_eq!(test.app.cursor, Duration::from_millis(240));
+
+        // starts a selection at the cursor
+        test.input("vb");
+        assert_eq!(
+            selection(&test),
+            (Duration::from_millis(240), Duration::from_millis(340))
+        );
+        assert_snapshot!("beat_grid", test.render());
+    }
+
+    fn write_wav(path: &std::path::Path, samples: &[i16]) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for s in samples {
+            writer.write_sample(*s).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_tui_empty_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.wav");
+        write_wav(&path, &[]);
+
+        let mut test = Test::open(path, Config::default());
+        assert!(test.app.status.as_ref().unwrap().contains("no audio"));
+        assert!(test.render().contains("contains no audio"));
+        test.input(&"z".repeat(20));
+        test.render();
+        test.input(&"Z".repeat(20));
+        test.render();
+        test.input("%");
+        test.render();
+        test.input(" ");
+        assert!(!test.app.playing);
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to play"));
+        test.input("lxgl");
+        test.render();
+        assert_eq!(test.app.cursor, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tui_one_sample_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("one.wav");
+        write_wav(&path, &[i16::MAX]);
+
+        let mut test = Test::open(path, Config::default());
+        assert_eq!(test.app.status, None);
+        test.input(&"z".repeat(20));
+        test.render();
+        test.input(&"Z".repeat(20));
+        test.render();
+        test.input("%");
+        test.render();
+        test.input("  ");
+        test.input("vlx");
+        test.render();
+    }
+
+    #[test]
+    fn test_tui_mono_monitor() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("cs");
+        assert_eq!(test.app.playback_source(Duration::ZERO).channels(), 2);
+
+        test.input("cM");
+        let playback = test.app.playback_source(Duration::from_millis(100));
+        assert_eq!(playback.channels(), 1);
+        assert_eq!(playback.total_duration(), Some(Duration::from_millis(400)));
+        assert_eq!(test.app.source.channels(), 2);
+        assert!(test.render().contains("atuio MONO"));
+
+        test.input("cM");
+        assert_eq!(test.app.playback_source(Duration::ZERO).channels(), 2);
+    }
+
+    #[test]
+    fn test_tui_log_panel() {
+        let mut test = Test::load("sine440.wav");
+        logger::RECORDS.push("first log line".into());
+        logger::RECORDS.push("second log line".into());
+        assert!(!test.render().contains("second log line"));
+
+        test.input("L");
+        let out = test.render();
+        assert!(out.contains("first log line"));
+        assert!(out.contains("second log line"));
+
+        test.input("K");
+        assert!(!test.render().contains("second log line"));
+        test.input("J");
+        assert!(test.render().contains("second log line"));
+
+        test.input("L");
+        assert!(!test.render().contains("second log line"));
+    }
+
+    #[test]
+    fn test_tui_selection_readout() {
+        let mut test = Test::load("sine440fade.wav");
+        assert!(test.app.selection_readout().is_none());
+
+        test.input("llllvlll");
+        assert_eq!(
+            test.app.selection_readout().unwrap(),
+            "0.040s - 0.070s (samples 1920 - 3360)"
+        );
+        assert!(test.render().contains("(samples 1920 - 3360)"));
+
+        // partial frames round to the nearest, where an edit would take effect
+        let pos = Duration::from_nanos(70_020_000);
+        test.app.move_cursor_to(pos);
+        assert_eq!(
+            test.app.selection_readout().unwrap(),
+            "0.040s - 0.070s (samples 1920 - 3361)"
+        );
+        assert_eq!(Audio::from(&test.app.source).index(pos), 3361);
+    }
+
+    #[test]
+    fn test_tui_unknown_duration() {
+        // from_iter can't know the total duration of the sources it chains
+        let source = rodio::source::from_iter([SamplesBuffer::new(1, 48000, vec![0.5f32; 4800])]);
+        assert_eq!(source.total_duration(), None);
+
+        let app = App::with_source(Config::default(), "unknown.wav".into(), source).unwrap();
+        let mut test = Test { app };
+        assert_eq!(test.app.window_end, Duration::from_millis(100));
+
+        test.input("gl");
+        assert_eq!(test.app.cursor, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_tui_scrub() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("l");
+        assert!(test.app.sink.empty());
+
+        test.input("wllll");
+        assert_eq!(test.app.sink.len(), 1);
+        assert_eq!(
+            test.app
+                .playback_range(test.app.cursor, test.app.cursor + SCRUB_GRAIN)
+                .total_duration(),
+            Some(SCRUB_GRAIN)
+        );
+
+        test.input("whhh");
+        assert!(test.app.sink.len() <= 1);
+    }
+
+    #[test]
+    fn test_tui_normalize_file() {
+        let peak = |app: &App| app.source.clone().fold(0.0f32, |max, v| max.max(v.abs()));
+        let mut test = Test::load("sine440fade.wav");
+        let before = peak(&test.app);
+
+        test.input("Ny");
+        let target = 10f32.powf(-1.0 / 20.0);
+        assert!((peak(&test.app) - target).abs() < 1e-6);
+        assert!(test
+            .app
+            .status
+            .as_ref()
+            .unwrap()
+            .starts_with("Normalized by +"));
+
+        test.input("o");
+        assert_eq!(peak(&test.app), before);
+        test.input("O");
+        assert!((peak(&test.app) - target).abs() < 1e-6);
+        test.input("O");
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to redo"));
+    }
+
+    #[test]
+    fn test_tui_undo() {
+        let mut test = Test::load("sine440fade.wav");
+        let original: Vec<_> = test.app.source.clone().collect();
+        test.input("o");
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to undo"));
+
+        test.input("llllvllllx");
+        test.input("cs");
+        assert_eq!(test.app.source.channels(), 2);
+
+        test.input("o");
+        assert_eq!(test.app.source.channels(), 1);
+        test.input("o");
+        assert_eq!(test.app.source.clone().collect::<Vec<_>>(), original);
+
+        test.input("O");
+        assert!(test.app.source.total_duration() < Some(Duration::from_millis(500)));
+
+        // a new edit discards the redo history
+        test.input("cs");
+        test.input("O");
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to redo"));
+    }
+
+    #[test]
+    fn test_tui_resize() {
+        let mut test = Test::load("sine440fade.wav");
+        let cached_width = |app: &App| app.wave_cache.borrow().as_ref().map(|c| c.width);
+        test.render();
+        // the plot is narrower than the terminal by the y-axis and its labels
+        assert_eq!(cached_width(&test.app), Some(154));
+
+        test.app.handle_event(Event::Resize(80, 20)).unwrap();
+        assert_eq!(cached_width(&test.app), None);
+
+        let mut buf = Buffer::empty(layout::Rect::new(0, 0, 80, 20));
+        test.app.render(buf.area, &mut buf);
+        assert_eq!(cached_width(&test.app), Some(74));
+        assert_eq!(
+            test.app.visible_wave_points(74),
+            test.app
+                .wave_points(test.app.window_start, test.app.window_end, 74, None)
+        );
+
+        // edits invalidate the cache too
+        test.input("cs");
+        assert_eq!(cached_width(&test.app), None);
+    }
+
+    #[test]
+    fn test_tui_clear_selection() {
+        let mut test = Test::load("sine440fade.wav");
+        let normal = |app: &App| matches!(app.mode, Mode::Normal);
+
+        test.input("\x1b");
+        assert!(normal(&test.app));
+
+        test.input("vll\x1b");
+        assert!(normal(&test.app));
+
+        // cancelling an effect goes back to the selection first
+        test.input("vllai\x1b");
+        assert!(matches!(test.app.mode, Mode::Select(_)));
+        test.input("\x1b");
+        assert!(normal(&test.app));
+        assert!(test.app.undo.is_empty());
+
+        test.input("t\x1b");
+        assert!(normal(&test.app));
+    }
+
+    #[test]
+    fn test_tui_undo_limit() {
+        let config = Config {
+            undo_limit: 2,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        test.input("cscmycscmy");
+        assert_eq!(test.app.undo.len(), 2);
+
+        test.input("ooo");
+        assert_eq!(test.app.source.channels(), 1);
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to undo"));
+        assert_eq!(test.app.redo.len(), 2);
+    }
+
+    #[test]
+    fn test_tui_limit_playback() {
+        let peak = |source: SamplesBuffer<f32>| source.fold(0.0f32, |max, v| max.max(v.abs()));
+        let mut test = Test::load("sine440fade.wav");
+        let mut audio = Audio::from(&test.app.source);
+        audio.apply(
+            Duration::ZERO,
+            audio.duration(),
+            &Effect::Amplify { amount: 4.0 },
+        );
+        test.app.set_source(audio.into());
+
+        assert!(peak(test.app.source.clone()) > 1.0);
+        assert!(peak(test.app.playback_source(Duration::ZERO)) > 1.0);
+
+        test.app.limit_playback = true;
+        assert!(peak(test.app.playback_source(Duration::ZERO)) <= 1.0);
+    }
+
+    #[test]
+    fn test_tui_center_on_cursor() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        test.app.move_cursor_to(ms(200));
+        test.app.window_start = ms(400);
+        test.app.window_end = ms(450);
+        test.input("C");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(175), ms(225))
+        );
+        assert_eq!(test.app.cursor, ms(200));
+
+        // clamped to the start and end of the file
+        test.app.move_cursor_to(ms(10));
+        test.input("C");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(0), ms(50))
+        );
+        test.app.move_cursor_to(ms(490));
+        test.input("C");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(450), ms(500))
+        );
+    }
+
+    #[test]
+    fn test_tui_insert_recording() {
+        let mut test = Test::load("sine440fade.wav");
+        test.app.move_cursor_to(Duration::from_millis(100));
+
+        // stereo at half the rate is converted to the file's mono 48k
+        let recording = Audio::new(2, 24000, vec![0.25; 2400 * 2]);
+        test.app.insert_recording(recording);
+        assert_eq!(test.app.source.channels(), 1);
+        assert_eq!(test.app.source.sample_rate(), 48000);
+        assert_eq!(
+            test.app.source.total_duration(),
+            Some(Duration::from_millis(600))
+        );
+        assert_eq!(test.app.cursor, Duration::from_millis(200));
+        let audio = Audio::from(&test.app.source);
+        let inserted = audio.slice(Duration::from_millis(100), Duration::from_millis(200));
+        assert!(inserted.iter().all(|&v| v == 0.25));
+        assert_eq!(test.app.status.as_deref(), Some("Recorded 0.10s"));
+    }
+
+    #[test]
+    fn test_tui_insert_recording_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.wav");
+        write_wav(&path, &[]);
+
+        let mut test = Test::open(path, Config::default());
+        test.app
+            .insert_recording(Audio::new(2, 44100, vec![0.5; 4410 * 2]));
+        assert_eq!(test.app.source.channels(), 2);
+        assert_eq!(test.app.source.sample_rate(), 44100);
+        assert_eq!(test.app.cursor, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_tui_duplicate_selection() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+
+        test.input("llllvlllD");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.source.total_duration(), Some(ms(530)));
+        assert_eq!(test.app.cursor, ms(100));
+
+        let after = Audio::from(&test.app.source);
+        assert_eq!(after.slice(ms(70), ms(100)), before.slice(ms(40), ms(70)));
+        assert_eq!(after.slice(ms(0), ms(70)), before.slice(ms(0), ms(70)));
+        assert_eq!(after.slice(ms(100), ms(530)), before.slice(ms(70), ms(500)));
+    }
+
+    #[test]
+    fn test_tui_apply_effect() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+
+        // preview doesn't touch the source, no matter how often it's drawn
+        test.input("llllvllllllaiiiiiiiiii");
+        test.render();
+        test.render();
+        assert_eq!(Audio::from(&test.app.source), before);
+
+        // 2x from 40-100ms
+        test.input("\n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.undo.len(), 1);
+        test.render();
+
+        // 0.5x from 70-130ms, previewed against the amplified audio
+        test.input("hhhvllllllauuuuu");
+        let Mode::Effect {
+            selection, effect, ..
+        } = &test.app.mode
+        else {
+            panic!("expected effect mode");
+        };
+        let (start, end) = selection.normalize();
+        let preview = test.app.wave_points(start, end, 160, Some(effect));
+        let current = test.app.wave_points(start, end, 160, None);
+        assert_eq!(preview.len(), current.len());
+        for ((_, a), (_, b)) in preview.iter().zip(current) {
+            assert!((a - b * 0.5).abs() < 1e-6);
+        }
+        test.input("\n");
+
+        let after = Audio::from(&test.app.source);
+        let assert_scaled = |start, end, gain: f32| {
+            let (start, end) = (ms(start), ms(end));
+            let expected = before.slice(start, end).iter().map(|v| v * gain);
+            for (a, b) in after.slice(start, end).iter().zip(expected) {
+                assert!((a - b).abs() < 1e-6, "{a} != {b}");
+            }
+        };
+        assert_eq!(after.samples.len(), before.samples.len());
+        assert_scaled(0, 40, 1.0);
+        assert_scaled(40, 70, 2.0);
+        assert_scaled(70, 100, 2.0 * 0.5);
+        assert_scaled(100, 130, 0.5);
+        assert_scaled(130, 500, 1.0);
+
+        test.input("oo");
+        assert_eq!(Audio::from(&test.app.source), before);
+    }
+
+    #[test]
+    fn test_tui_export_view() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.wav");
+        std::fs::copy("testdata/sine440fade.wav", &path).unwrap();
+        let mut test = Test::open(path, Config::default());
+        test.app.window_start = Duration::from_millis(100);
+        test.app.window_end = Duration::from_millis(250);
+
+        test.input("E");
+        let out = dir.path().join("fade_0.100-0.250.wav");
+        assert_eq!(test.app.status, Some(format!("Exported {}", out.display())));
+        let reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.duration(), 48000 * 150 / 1000);
+        // the original is untouched
+        assert_eq!(test.app.path, dir.path().join("fade.wav"));
+    }
+
+    #[test]
+    fn test_tui_snap() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            bpm: Some(600.0),
+            snap: true,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+
+        // jumps land exactly where they aim, off the grid
+        test.app.apply_action(Action::SeekPercent(33)).unwrap();
+        assert_eq!(test.app.cursor, ms(165));
+        test.app.apply_action(Action::CursorEnd).unwrap();
+        assert_eq!(test.app.cursor, test.app.duration());
+
+        // stepping moves between gridlines
+        test.app.move_cursor_to(ms(130));
+        test.input("h");
+        assert_eq!(test.app.cursor, ms(100));
+        test.input("l");
+        assert_eq!(test.app.cursor, ms(200));
+
+        test.input("vll");
+        let Mode::Select(sel) = &test.app.mode else {
+            panic!("expected select mode");
+        };
+        assert_eq!(sel.normalize(), (ms(200), ms(400)));
+        test.input("h");
+        assert_eq!(test.app.cursor, ms(300));
+
+        test.input("\x1bB");
+        assert_eq!(test.app.status.as_deref(), Some("Snap to grid off"));
+        test.input("l");
+        assert_eq!(test.app.cursor, ms(310));
+    }
+
+    #[test]
+    fn test_tui_seek_percent() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("g5");
+        assert_eq!(test.app.cursor, Duration::from_millis(250));
+        test.input("g1");
+        assert_eq!(test.app.cursor, Duration::from_millis(50));
+        test.app.apply_action(Action::SeekPercent(200)).unwrap();
+        assert_eq!(test.app.cursor, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_tui_amplify_whole_file() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+        test.input("lllaiiiii\ny");
+        let after = Audio::from(&test.app.source);
+        assert_eq!(after.samples.len(), before.samples.len());
+        for (a, b) in after.samples.iter().zip(&before.samples) {
+            assert!((a - b * 1.5).abs() < 1e-6, "{a} != {b} * 1.5");
+        }
+        assert_eq!(test.app.cursor, Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_tui_decode_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = |path: &std::path::Path| {
+            App::new(Config::default(), path.to_path_buf())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        let missing = dir.path().join("missing.wav");
+        assert_eq!(err(&missing), format!("{} not found", missing.display()));
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "not audio at all").unwrap();
+        assert_eq!(
+            err(&text),
+            format!(
+                "{} is not in a supported audio format (wav, flac, ogg vorbis, mp3)",
+                text.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_tui_sample_readout() {
+        // 4 stereo frames at 100hz, so each frame is 10ms
+        let samples = vec![0.0, 0.5, 0.25, -0.25, -1.0, 1.0, 0.125, 0.0];
+        let source = SamplesBuffer::new(2, 100, samples);
+        let app = App::with_source(Config::default(), "test.wav".into(), source).unwrap();
+        let mut test = Test { app };
+        assert_eq!(test.app.sample_readout(), None);
+
+        test.input("#");
+        assert_eq!(
+            test.app.sample_readout().as_deref(),
+            Some("sample 0: +0.0000 +0.5000")
+        );
+        test.input("ll");
+        assert_eq!(
+            test.app.sample_readout().as_deref(),
+            Some("sample 2: -1.0000 +1.0000")
+        );
+        assert!(test.render().contains("sample 2: -1.0000 +1.0000"));
+
+        test.input("gl");
+        assert_eq!(test.app.sample_readout().as_deref(), Some("sample 4: end"));
+    }
+
+    #[test]
+    fn test_tui_play_loop() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let audio = Audio::from(&test.app.source);
+
+        let looped = Audio::from(&test.app.loop_source(ms(100), ms(200)));
+        // the last 10ms are faded into the first 10ms
+        assert_eq!(looped.duration(), ms(90));
+        let region = audio.slice(ms(100), ms(200));
+        let tail = audio.slice(ms(190), ms(200));
+        assert_eq!(looped.samples[0], tail[0]);
+        assert_eq!(looped.samples.last(), audio.slice(ms(100), ms(190)).last());
+        let mid = tail.len() / 2;
+        let expected = (region[mid] + tail[mid]) / 2.0;
+        assert!((looped.samples[mid] - expected).abs() < 1e-6);
+
+        test.input("llllllllllvllllllllllP");
+        assert!(test.app.playing);
+        assert_eq!(test.app.looping, Some((ms(100), ms(90))));
+
+        test.input("P");
+        assert!(!test.app.playing);
+        assert_eq!(test.app.looping, None);
+    }
+
+    #[test]
+    fn test_tui_revert() {
+        let mut test = Test::load("sine440fade.wav");
+        let original = Audio::from(&test.app.source);
+        test.input("llllvllllxcszz");
+        assert_ne!(Audio::from(&test.app.source), original);
+
+        // cancelling keeps the edits
+        test.input("!n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.source.channels(), 2);
+
+        test.input("!");
+        assert!(test.render().contains("discard all changes? (y/n)"));
+        test.input("y");
+        assert_eq!(Audio::from(&test.app.source), original);
+        assert_eq!(test.app.cursor, Duration::ZERO);
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (Duration::ZERO, Duration::from_millis(500))
+        );
+        assert!(test.app.undo.is_empty() && test.app.redo.is_empty());
+        test.input("o");
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to undo"));
+    }
+
+    #[test]
+    fn test_tui_quit_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.wav");
+        std::fs::copy("testdata/sine440fade.wav", &path).unwrap();
+        let mut test = Test::open(path.clone(), Config::default());
+
+        // nothing to lose yet
+        assert!(!test.app.dirty);
+        test.input("a\x1b");
+        assert!(!test.app.dirty);
+
+        test.input("llllvllllx");
+        assert!(test.app.dirty);
+        test.input("q");
+        assert!(!test.app.exit);
+        assert!(test
+            .render()
+            .contains("save changes before quitting? (y)es/(n)o/(c)ancel"));
+        test.input("c");
+        assert!(!test.app.exit);
+        assert!(matches!(test.app.mode, Mode::Normal));
+
+        // quitting after saving is allowed
+        test.input("s");
+        assert!(!test.app.dirty);
+        test.input("q");
+        assert!(test.app.exit);
+
+        // saving from the prompt
+        let mut test = Test::open(path.clone(), Config::default());
+        test.input("llllvllllxqy");
+        assert!(test.app.exit);
+        assert_eq!(
+            hound::WavReader::open(&path).unwrap().len() as usize,
+            test.app.source.clone().count()
+        );
+
+        // discarding from the prompt
+        let mut test = Test::open(path.clone(), Config::default());
+        let before = hound::WavReader::open(&path).unwrap().len();
+        test.input("llllvllllxq");
+        assert!(!test.app.exit);
+        assert!(test
+            .render()
+            .contains("save changes before quitting? (y)es/(n)o/(c)ancel"));
+        test.input("n");
+        assert!(test.app.exit);
+        assert_eq!(hound::WavReader::open(&path).unwrap().len(), before);
+
+        // force quit
+        let mut test = Test::open(path, Config::default());
+        test.input("llllvllllx");
+        test.input("Q");
+        assert!(test.app.exit);
+    }
+
+    #[test]
+    fn test_tui_y_ticks() {
+        assert_eq!(amplitude_labels(-1.0, 1.0, 3), ["-1.0", "0.0", "1.0"]);
+        assert_eq!(
+            amplitude_labels(-1.0, 1.0, 9),
+            ["-1.00", "-0.75", "-0.50", "-0.25", "0.00", "0.25", "0.50", "0.75", "1.00"]
+        );
+
+        let config = Config {
+            y_ticks: 5,
+            ..Default::default()
+        };
+        let test = Test::load_with_config("sine440fade.wav", config);
+        assert_snapshot!(test.render());
+    }
+
+    #[test]
+    fn test_tui_select_to_marker() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let selection = |test: &Test| match &test.app.mode {
+            Mode::Select(sel) => sel.normalize(),
+            _ => panic!("Not selecting"),
+        };
+
+        test.input("llllm\n");
+        test.input("llllllllm\n");
+        test.input("gslllllll");
+
+        test.input("}");
+        assert_eq!(selection(&test), (ms(70), ms(120)));
+        assert_eq!(test.app.cursor, ms(120));
+
+        // there is no marker past the last one
+        test.input("}");
+        assert_eq!(selection(&test), (ms(70), ms(120)));
+
+        // extends the existing selection back across the start
+        test.input("{{");
+        assert_eq!(selection(&test), (ms(40), ms(70)));
+        assert_eq!(test.app.cursor, ms(40));
+
+        test.input("\x1bgs{");
+        assert!(matches!(test.app.mode, Mode::Normal));
+    }
+
+    #[test]
+    fn test_tui_jump_back() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+
+        test.input("`");
+        assert_eq!(test.app.status.as_deref(), Some("No previous position"));
+
+        test.input("lll");
+        test.input("gl");
+        assert_eq!(test.app.cursor, ms(500));
+        test.input("`");
+        assert_eq!(test.app.cursor, ms(30));
+        // toggles between the two
+        test.input("`");
+        assert_eq!(test.app.cursor, ms(500));
+
+        // small steps don't count as jumps
+        test.input("hh`");
+        assert_eq!(test.app.cursor, ms(30));
+
+        test.input("lmfirst\n");
+        test.input("gs/fir\n");
+        assert_eq!(test.app.cursor, ms(40));
+        test.input("`");
+        assert_eq!(test.app.cursor, ms(0));
+    }
+
+    #[test]
+    fn test_tui_click() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("silence.wav");
+        write_wav(&path, &[0; 48000]);
+
+        let mut test = Test::open(path.clone(), Config::default());
+        test.input("k");
+        assert!(!test.app.click);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Set a bpm to use the click")
+        );
+
+        let config = Config {
+            bpm: Some(600.0),
+            ..Default::default()
+        };
+        let mut test = Test::open(path, config);
+        test.input("k");
+        assert!(test.app.click);
+
+        // beats fall every 100ms, so 50ms and 150ms into playback from 50ms
+        let playback: Vec<_> = test
+            .app
+            .playback_source(Duration::from_millis(50))
+            .collect();
+        let onsets: Vec<_> = (0..playback.len())
+            .filter(|&i| playback[i] != 0.0 && (i == 0 || playback[i - 1] == 0.0))
+            .collect();
+        assert_eq!(onsets[..3], [2400, 7200, 12000]);
+        assert_eq!(onsets.len(), 9);
+        // only the playback is affected
+        assert!(test.app.source.clone().all(|v| v == 0.0));
+
+        test.input("k");
+        assert!(test
+            .app
+            .playback_source(Duration::from_millis(50))
+            .all(|v| v == 0.0));
+    }
+
+    #[test]
+    fn test_tui_reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("silence.wav");
+        write_wav(&path, &[0; 24000]);
+        let mut test = Test::open(path, Config::default());
+        let without = test.render();
+
+        test.input("Ftestdata/missing.wav\n");
+        assert!(test.app.reference.is_none());
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot load reference: testdata/missing.wav not found")
+        );
+
+        test.input("Ftestdata/sine440fade.wav\n");
+        assert!(test.app.reference.is_some());
+        let with = test.render();
+        assert_ne!(with, without);
+        // the flat source is still drawn, with the reference around it
+        assert!(with.lines().filter(|l| l.contains('⣿')).count() > 5);
+
+        // edits only apply to the source
+        test.input("%x");
+        assert_eq!(test.app.duration(), Duration::ZERO);
+        assert_eq!(
+            test.app.reference.as_ref().unwrap().total_duration(),
+            Some(Duration::from_millis(500))
+        );
+
+        test.input("F\n");
+        assert!(test.app.reference.is_none());
+    }
+
+    #[test]
+    fn test_tui_snap_to_frames() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            fps: 25.0,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+
+        test.input("|");
+        assert!(matches!(test.app.mode, Mode::Normal));
+
+        test.input("lllvlllllllllllll");
+        test.input("|");
+        let Mode::Select(sel) = &test.app.mode else {
+            panic!("Not selecting");
+        };
+        // 30ms and 160ms round to the nearest 40ms frame
+        assert_eq!(sel.normalize(), (ms(40), ms(160)));
+        assert_eq!(test.app.cursor, ms(160));
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Selected frames 1-4 at 25fps")
+        );
+    }
+
+    #[test]
+    fn test_tui_play_clock() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut clock = PlayClock::new(ms(0), start);
+
+        // advances between updates from the sink
+        assert_eq!(clock.position(ms(0), start + ms(10)), ms(10));
+        assert_eq!(clock.position(ms(0), start + ms(30)), ms(30));
+        // and resyncs when the sink reports a new position
+        assert_eq!(clock.position(ms(20), start + ms(40)), ms(20));
+        assert_eq!(clock.position(ms(20), start + ms(55)), ms(35));
+        // but doesn't run far ahead of a stalled sink
+        assert_eq!(clock.position(ms(20), start + ms(1000)), ms(270));
+
+        let mut test = Test::load("sine440fade.wav");
+        assert_eq!(test.app.play_position(ms(0), start), ms(0));
+        assert_eq!(test.app.play_position(ms(0), start + ms(15)), ms(15));
+
+        let config = Config {
+            smooth_playhead: false,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        assert_eq!(test.app.play_position(ms(0), start), ms(0));
+        assert_eq!(test.app.play_position(ms(0), start + ms(15)), ms(0));
+    }
+
+    #[test]
+    fn test_tui_snap_cuts_to_zero() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            snap_cuts_to_zero: true,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        let original = Audio::from(&test.app.source);
+        let start = original.nearest_zero_crossing(ms(30));
+        let end = original.nearest_zero_crossing(ms(70));
+        // a 440hz sine doesn't cross zero on these exact times
+        assert_ne!((start, end), (ms(30), ms(70)));
+        for pos in [start, end] {
+            let v = original.samples[original.index(pos)];
+            assert!(v.abs() < 0.05, "{v} at {pos:?} is not near zero");
+        }
+
+        test.input("lllvllllx");
+        let mut expected = original.clone();
+        expected.cut(start, end);
+        assert_eq!(Audio::from(&test.app.source), expected);
+        assert_eq!(test.app.cursor, start);
+
+        // disabled by default
+        let mut test = Test::load("sine440fade.wav");
+        test.input("lllvllllx");
+        let mut expected = original.clone();
+        expected.cut(ms(30), ms(70));
+        assert_eq!(Audio::from(&test.app.source), expected);
+    }
+
+    #[test]
+    fn test_tui_edit_count() {
+        let mut test = Test::load("sine440fade.wav");
+        assert_eq!(test.app.edits, 0);
+
+        test.input("llllvllllx");
+        assert_eq!(test.app.edits, 1);
+
+        test.input("a");
+        test.input(&"i".repeat(10));
+        test.input("\ny");
+        assert_eq!(test.app.edits, 2);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Applied Amplify +6.0dB (edit 2)")
+        );
+
+        // amplifying the same region again reports the total gain
+        test.input("a");
+        test.input(&"i".repeat(10));
+        test.input("\ny");
+        assert_eq!(test.app.edits, 3);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Applied Amplify +6.0dB (edit 3), +12.0dB total on this selection")
+        );
+
+        // any other edit starts the total over
+        test.input("cs");
+        assert_eq!(test.app.edits, 4);
+        test.input("v%a");
+        test.input(&"i".repeat(10));
+        test.input("\ny");
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Applied Amplify +6.0dB (edit 5)")
+        );
+    }
+
+    #[test]
+    fn test_tui_tap_tempo() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let start = Instant::now();
+
+        test.app.tap_tempo(start);
+        assert_eq!(test.app.bpm, None);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Tap again to set the tempo")
+        );
+
+        // slightly uneven taps around 120bpm
+        for t in [510, 990, 1500, 2010] {
+            test.app.tap_tempo(start + ms(t));
+        }
+        let bpm = test.app.bpm.unwrap();
+        assert!((bpm - 119.4).abs() < 0.1, "{bpm}");
+        assert_eq!(test.app.status.as_deref(), Some("119.4 bpm"));
+        assert_eq!(test.app.beat(), Some(Duration::from_secs_f64(60.0 / bpm)));
+
+        // a pause starts over
+        test.app.tap_tempo(start + ms(5000));
+        test.app.tap_tempo(start + ms(5250));
+        assert_eq!(test.app.bpm, Some(240.0));
+    }
+
+    #[test]
+    fn test_tui_sample_axis() {
+        let config = Config {
+            sample_axis: true,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        test.input("lllllvlllll");
+        assert_snapshot!(test.render());
+
+        // the same view in seconds, apart from the labels
+        test.input("X");
+        let secs = test.render();
+        assert!(secs.contains("0.5s"));
+        test.input("X");
+        assert!(test.render().contains("24000"));
+    }
+
+    #[test]
+    fn test_tui_effect_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.wav");
+        std::fs::copy("testdata/sine440fade.wav", &path).unwrap();
+        let mut test = Test::open(path, Config::default());
+        let original = Audio::from(&test.app.source);
+
+        test.input("-");
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("The effect chain is empty")
+        );
+
+        // halve, then amplify by 1.5
+        test.input("auuuuu+");
+        test.input("aiiiii+");
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Added Amplify +3.5dB to the effect chain")
+        );
+        assert!(matches!(test.app.mode, Mode::Normal));
+        let out = test.render();
+        assert!(out.contains("effect chain"), "{out}");
+        assert!(out.contains("Amplify -6.0dB"), "{out}");
+        // the source is untouched
+        assert_eq!(Audio::from(&test.app.source), original);
+
+        test.input("E");
+        let exported = dir.path().join("fade_0.000-0.500.wav");
+        let samples: Vec<i16> = hound::WavReader::open(&exported)
+            .unwrap()
+            .samples()
+            .map(Result::unwrap)
+            .collect();
+        let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        let original_peak = original
+            .samples
+            .iter()
+            .fold(0.0f32, |max, v| max.max(v.abs()));
+        let expected = original_peak * 0.75 * i16::MAX as f32;
+        assert!((peak as f32 - expected).abs() < 2.0, "{peak} != {expected}");
+
+        // popping removes the last effect
+        test.input("-");
+        assert_eq!(test.app.effect_chain.len(), 1);
+        assert!(test.app.dirty);
+
+        // saving applies the chain once, and it can be undone
+        let halved = |test: &Test| {
+            let samples = Audio::from(&test.app.source).samples;
+            samples
+                .iter()
+                .zip(&original.samples)
+                .all(|(a, b)| (a - b * 0.5).abs() < 1e-6)
+        };
+        test.input("s");
+        assert!(!test.app.dirty);
+        assert!(test.app.effect_chain.is_empty());
+        assert!(halved(&test));
+        test.input("s");
+        assert!(halved(&test));
+        let saved = load(&test.app.path).unwrap();
+        let peak = saved.fold(0.0f32, |max, v| max.max(v.abs()));
+        assert!((peak - original_peak * 0.5).abs() < 1e-3, "{peak}");
+        test.input("o");
+        assert_eq!(Audio::from(&test.app.source), original);
+
+        // a selection can't be pushed, as the chain would apply it to the whole file
+        test.input("vlai+");
+        assert!(test.app.effect_chain.is_empty());
+        assert!(matches!(test.app.mode, Mode::Effect { .. }));
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("The effect chain applies to the whole file, not a selection")
+        );
+    }
+
+    #[test]
+    fn test_tui_echo() {
+        let mut test = Test::load("sine440fade.wav");
+        let params = |test: &Test| match test.app.mode {
+            Mode::Effect {
+                effect: Effect::Echo { delay, feedback },
+                ..
+            } => (delay, feedback),
+            _ => panic!("Not in echo mode"),
+        };
+
+        test.input("lvlle");
+        assert_eq!(params(&test), (0.25, 0.5));
+        test.input("u");
+        assert_eq!(params(&test), (0.15, 0.5));
+        test.input("[[");
+        let (delay, feedback) = params(&test);
+        assert_eq!(delay, 0.15);
+        assert!((feedback - 0.3).abs() < 1e-6, "{feedback}");
+        test.input("]I");
+        let (delay, feedback) = params(&test);
+        assert!((delay - 0.16).abs() < 1e-6, "{delay}");
+        assert!((feedback - 0.4).abs() < 1e-6, "{feedback}");
+        assert!(test.render().contains("Echo 160ms 40% feedback"));
+
+        // the secondary parameter does nothing for effects without one
+        test.input("\x1bva]");
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Only the gain can be adjusted")
+        );
+        assert!(matches!(
+            test.app.mode,
+            Mode::Effect {
+                effect: Effect::Amplify { amount: 1.0 },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tui_paste_path() {
+        let mut test = Test::load("sine440fade.wav");
+        assert!(test.app.reference.is_none());
+
+        test.app
+            .handle_event(Event::Paste("/does/not/exist.wav".into()))
+            .unwrap();
+        assert!(test.app.reference.is_none());
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Not a file: /does/not/exist.wav")
+        );
+
+        // terminals may quote the dropped path
+        let path = std::fs::canonicalize("testdata/sine440fade.wav").unwrap();
+        let text = format!("'{}' ", path.display());
+        test.app.handle_event(Event::Paste(text)).unwrap();
+        assert!(test.app.reference.is_some());
+
+        // in a prompt, the text is typed instead
+        test.input("F");
+        test.app.handle_event(Event::Paste("a b".into())).unwrap();
+        let Mode::Prompt { input, .. } = &test.app.mode else {
+            panic!("expected prompt");
+        };
+        assert_eq!(input, "a b");
+    }
+
+    #[test]
+    fn test_dropped_path() {
+        use std::path::PathBuf;
+        assert_eq!(dropped_path("/a/b.wav"), PathBuf::from("/a/b.wav"));
+        assert_eq!(dropped_path("'/a b/c.wav'\n"), PathBuf::from("/a b/c.wav"));
+        assert_eq!(dropped_path("\"/a b/c.wav\""), PathBuf::from("/a b/c.wav"));
+        assert_eq!(dropped_path("/a\\ b/c.wav"), PathBuf::from("/a b/c.wav"));
+        assert_eq!(dropped_path("file:///a/b.wav"), PathBuf::from("/a/b.wav"));
+    }
+
+    #[test]
+    fn test_tui_micro_fade() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                micro_fade: 20,
+                ..Default::default()
+            },
+        );
+        let before = Audio::from(&test.app.source);
+
+        // fade 100-200ms
+        test.input("llllllllllvllllllllllf");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        let after = Audio::from(&test.app.source);
+        let ramp = |start, end| {
+            let (start, end) = (ms(start), ms(end));
+            before
+                .slice(start, end)
+                .iter()
+                .zip(after.slice(start, end))
+                .filter(|(b, _)| b.abs() > 1e-3)
+                .map(|(b, a)| a / b)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(ramp(0, 100).iter().all(|&g| g == 1.0));
+        assert!(ramp(120, 180).iter().all(|&g| g == 1.0));
+        assert!(ramp(200, 500).iter().all(|&g| g == 1.0));
+        let fade_in = ramp(100, 120);
+        assert!(fade_in[0] < 0.1, "{fade_in:?}");
+        assert!(fade_in.windows(2).all(|w| w[0] <= w[1] + 1e-3));
+        let fade_out = ramp(180, 200);
+        assert!(*fade_out.last().unwrap() < 0.1, "{fade_out:?}");
+        assert!(fade_out.windows(2).all(|w| w[0] + 1e-3 >= w[1]));
+    }
+
+    #[test]
+    fn test_tui_paste_sample_rate() {
+        let mut test = Test::load("sine440fade.wav");
+        let len = test.app.duration();
+        let clip = |rate: u32| Audio::new(1, rate, vec![0.5; rate as usize / 10]);
+        assert_eq!(test.app.source.sample_rate(), 48000);
+
+        // 100ms at 24kHz is resampled to 100ms at 48kHz
+        test.app.clipboard = Some(clip(24000));
+        test.input("p");
+        assert_eq!(test.app.duration(), len + Duration::from_millis(100));
+        assert_eq!(test.app.source.sample_rate(), 48000);
+
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                conform_paste: false,
+                ..Default::default()
+            },
+        );
+        test.app.clipboard = Some(clip(24000));
+        test.input("p");
+        assert_eq!(test.app.duration(), len);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot paste: Clipboard is 24000Hz 1ch but the file is 48000Hz 1ch")
+        );
+        assert!(test.app.undo.is_empty());
+
+        // matching audio is pasted either way
+        test.app.clipboard = Some(clip(48000));
+        test.input("p");
+        assert_eq!(test.app.duration(), len + Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_tui_max_fps() {
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                max_fps: 10.0,
+                ..Default::default()
+            },
+        );
+        let ms = Duration::from_millis;
+        let now = Instant::now();
+        assert!(test.app.frame_due(now));
+        test.app.drawn(now);
+
+        // nothing changed
+        assert!(!test.app.frame_due(now + ms(500)));
+        test.app.handle_event(Event::FocusGained).unwrap();
+        assert!(!test.app.frame_due(now + ms(500)));
+
+        // changed, but too soon after the last draw
+        test.input("l");
+        assert!(!test.app.frame_due(now + ms(50)));
+        assert!(test.app.frame_due(now + ms(100)));
+        test.app.drawn(now + ms(100));
+        assert!(!test.app.frame_due(now + ms(500)));
+
+        // no limit
+        let mut test = Test::load_with_config(
+            "sine440fade.wav",
+            Config {
+                max_fps: 0.0,
+                ..Default::default()
+            },
+        );
+        test.app.drawn(now);
+        test.input("l");
+        assert!(test.app.frame_due(now));
+    }
+
+    #[test]
+    fn test_tui_hide_instructions() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("H");
+        assert_snapshot!(test.render());
+        test.input("H");
+        assert!(test.render().contains("Quit <Q>"));
+    }
+
+    #[test]
+    fn test_tui_select_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.wav");
+        // three 100ms "words" separated by 100ms of silence
+        let tone = |freq| edit::tone(freq, Duration::from_millis(100), 1, 1000);
+        let silence = vec![0.0; 100];
+        let samples = [
+            tone(100.0),
+            silence.clone(),
+            tone(200.0),
+            silence,
+            tone(100.0),
+        ]
+        .concat();
+        let audio = Audio::new(1, 1000, samples);
+        encode::write(&path, &audio.into(), WavBits::Float32).unwrap();
+        let mut test = Test::open(path, Config::default());
+        let ms = Duration::from_millis;
+
+        // in the second word
+        test.app.move_cursor_to(ms(250));
+        test.input("W");
+        let Mode::Select(sel) = &test.app.mode else {
+            panic!("expected selection");
+        };
+        let (start, end) = sel.normalize();
+        assert!(start.abs_diff(ms(200)) <= ms(2), "{start:?}");
+        assert!(end.abs_diff(ms(300)) <= ms(2), "{end:?}");
+
+        // in a gap
+        test.input("\x1b");
+        test.app.move_cursor_to(ms(150));
+        test.input("W");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.status.as_deref(), Some("No sound at the cursor"));
+
+        test.input(")");
+        assert!(test.app.cursor.abs_diff(ms(200)) <= ms(2));
+        test.input(")");
+        assert!(test.app.cursor.abs_diff(ms(400)) <= ms(2));
+        test.input("((");
+        assert!(test.app.cursor <= ms(2));
+    }
+
+    #[test]
+    fn test_tui_minimap() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("zz");
+        test.input(&"l".repeat(25));
+        test.input("C");
+        assert!(test.app.window_start > Duration::ZERO);
+        assert_snapshot!(test.render());
+    }
+
+    #[test]
+    fn test_tui_selection_to_loop() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+
+        test.input("llllllllllvllllllllllV");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.loop_region, Some((ms(100), ms(200))));
+        assert_eq!(test.app.status.as_deref(), Some("Looping 0.100s - 0.200s"));
+
+        // the loop is used without a selection, wherever the cursor is
+        test.input("llP");
+        assert_eq!(test.app.looping, Some((ms(100), ms(90))));
+        test.input("P");
+
+        // a selection still takes precedence
+        test.input("vllP");
+        assert_eq!(test.app.looping, Some((ms(220), ms(10))));
+        test.input("P\x1b");
+
+        test.input("V");
+        assert_eq!(test.app.loop_region, None);
+        test.input("P");
+        assert_eq!(test.app.looping.map(|(start, _)| start), Some(ms(0)));
+    }
+
+    #[test]
+    fn test_tui_macro() {
+        use crate::binds::BindMap;
+        let ms = Duration::from_millis;
+        let steps = vec![
+            (Action::CursorRight, Some(ms(100))),
+            (Action::CursorRight, None),
+            (Action::Select, Some(ms(50))),
+            (Action::CursorRight, None),
+        ];
+        let mut config = Config::default();
+        config.binds.overlay(BindMap::new([(
+            KeyEvent::from(KeyCode::Char('@')),
+            Binding::Macro(steps.clone()),
+        )]));
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+
+        // the first step runs straight away
+        test.input("@");
+        assert_eq!(test.app.cursor, ms(10));
+        assert_eq!(test.app.scheduled.len(), 3);
+
+        // the rest run when due, in order
+        test.app.scheduled.clear();
+        test.app.move_cursor_to(Duration::ZERO);
+        let now = Instant::now();
+        test.app.start_macro(&steps, now).unwrap();
+        assert_eq!(test.app.cursor, ms(10));
+        test.app.run_scheduled(now).unwrap();
+        assert_eq!(test.app.cursor, ms(10));
+        test.app.run_scheduled(now + ms(100)).unwrap();
+        assert_eq!(test.app.cursor, ms(20));
+        assert!(matches!(test.app.mode, Mode::Select(_)));
+        test.app.run_scheduled(now + ms(140)).unwrap();
+        assert_eq!(test.app.cursor, ms(20));
+        test.app.run_scheduled(now + ms(150)).unwrap();
+        assert_eq!(test.app.cursor, ms(30));
+        assert!(test.app.scheduled.is_empty());
+
+        // keys still work while a macro is waiting
+        let start = Instant::now();
+        test.app
+            .schedule(&[(Action::CursorStart, None)], start + ms(100));
+        test.input("l");
+        assert_eq!(test.app.cursor, ms(40));
+        test.app.run_scheduled(start + ms(100)).unwrap();
+        assert_eq!(test.app.cursor, ms(0));
+    }
+
+    #[test]
+    fn test_tui_effect_limit() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("a");
+        test.input(&"u".repeat(30));
+        assert!(matches!(
+            test.app.mode,
+            Mode::Effect {
+                effect: Effect::Amplify { amount: 0.0 },
+                ..
+            }
+        ));
+        assert!(test.render().contains("Amplify -inf dB (limit)"));
+
+        test.input("i");
+        assert!(!test.render().contains("(limit)"));
+    }
+
+    #[test]
+    fn test_tui_go_to_sample() {
+        let mut test = Test::load("sine440fade.wav");
+        assert_eq!(test.app.source.sample_rate(), 48000);
+
+        test.input("gi");
+        assert!(test.render().contains("go to sample: "));
+        test.input("12000\n");
+        assert_eq!(test.app.cursor, Duration::from_millis(250));
+        assert_eq!(test.app.frame_at(test.app.cursor), 12000);
+
+        test.input("gi1\n");
+        assert_eq!(test.app.frame_at(test.app.cursor), 1);
+
+        // out of range or not a number leaves the cursor alone
+        test.input("gi24001\n");
+        assert_eq!(test.app.frame_at(test.app.cursor), 1);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Sample must be at most 24000")
+        );
+        test.input("gi-5\n");
+        assert_eq!(test.app.frame_at(test.app.cursor), 1);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Not a sample index: \"-5\"")
+        );
+    }
+
+    #[test]
+    fn test_tui_nudge() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+        // 1ms at 48kHz
+        let frames = 48;
+
+        // 100-200ms, nudged right twice
+        test.input(&"l".repeat(10));
+        test.input("v");
+        test.input(&"l".repeat(10));
+        test.input(">>");
+        assert!(matches!(test.app.mode, Mode::Select(_)));
+        let after = Audio::from(&test.app.source);
+        let (start, end) = (before.index(ms(100)), before.index(ms(200)));
+        assert_eq!(after.samples[..start], before.samples[..start]);
+        assert_eq!(after.samples[end..], before.samples[end..]);
+        assert!(after.samples[start..start + 2 * frames]
+            .iter()
+            .all(|&v| v == 0.0));
+        assert_eq!(
+            after.samples[start + 2 * frames..end],
+            before.samples[start..end - 2 * frames]
+        );
+
+        // and back, losing what was pushed out of the selection
+        test.input("<");
+        let after = Audio::from(&test.app.source);
+        assert_eq!(
+            after.samples[start + frames..end - frames],
+            before.samples[start..end - 2 * frames]
+        );
+        assert!(after.samples[end - frames..end].iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn test_tui_fast_zoom() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        let width = |test: &Test| test.app.window_end - test.app.window_start;
+        let alt = |c| KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::ALT);
+        assert_eq!(width(&test), ms(500));
+
+        test.app.handle_event(Event::Key(alt('z'))).unwrap();
+        assert_eq!(width(&test), ms(125));
+        test.app.handle_event(Event::Key(alt('z'))).unwrap();
+        assert_eq!(width(&test), Duration::from_micros(31250));
+        test.app.handle_event(Event::Key(alt('Z'))).unwrap();
+        assert_eq!(width(&test), ms(125));
+
+        // the fine zoom is unchanged
+        test.input("z");
+        assert_eq!(width(&test), ms(25));
+
+        // never narrower than 1ms
+        for _ in 0..10 {
+            test.app.handle_event(Event::Key(alt('z'))).unwrap();
+        }
+        assert_eq!(width(&test), ms(1));
+    }
+
+    #[test]
+    fn test_tui_cancel_effect() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+
+        test.input("llvlllaii\x1b");
+        let Mode::Select(sel) = &test.app.mode else {
+            panic!("expected selection");
+        };
+        assert_eq!(
+            sel.normalize(),
+            (Duration::from_millis(20), Duration::from_millis(50))
+        );
+        assert_eq!(Audio::from(&test.app.source), before);
+
+        // a second escape clears it
+        test.input("\x1b");
+        assert!(matches!(test.app.mode, Mode::Normal));
+
+        // an effect on the whole file returns to no selection
+        test.input("a\x1b");
+        assert!(matches!(test.app.mode, Mode::Normal));
+    }
+
+    #[test]
+    fn test_tui_export_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.wav");
+        std::fs::copy("testdata/sine440fade.wav", &path).unwrap();
+        let mut test = Test::open(path, Config::default());
+        let alt_e = KeyEvent::new(KeyCode::Char('e'), crossterm::event::KeyModifiers::ALT);
+
+        test.app.handle_event(Event::Key(alt_e)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("No markers to export"));
+
+        test.input("llllmverse\n");
+        test.input("llllm\n");
+        test.app.handle_event(Event::Key(alt_e)).unwrap();
+        let text = std::fs::read_to_string(dir.path().join("fade.labels.txt")).unwrap();
+        let labels = labels::parse(&text).unwrap();
+        let markers: Vec<_> = test
+            .app
+            .markers
+            .iter()
+            .map(|m| labels::Label {
+                start: m.pos,
+                end: m.pos,
+                text: m.label.clone().unwrap_or_default(),
+            })
+            .collect();
+        assert_eq!(labels, markers);
+    }
+
+    #[test]
+    fn test_tui_import_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let labels = dir.path().join("labels.txt");
+        let import = |test: &mut Test, path: &std::path::Path| {
+            let key = KeyEvent::new(KeyCode::Char('i'), crossterm::event::KeyModifiers::ALT);
+            test.app.handle_event(Event::Key(key)).unwrap();
+            let Mode::Prompt { input, .. } = &mut test.app.mode else {
+                panic!("expected prompt");
+            };
+            *input = path.display().to_string();
+            test.input("\n");
+        };
+        let mut test = Test::load("sine440fade.wav");
+        let markers = |test: &Test| -> Vec<_> {
+            test.app
+                .markers
+                .iter()
+                .map(|m| (m.pos.as_millis(), m.label.clone()))
+                .collect()
+        };
+
+        std::fs::write(&labels, "0.1\t0.1\tverse\n0.2\t0.35\tchorus\n0.4\t0.4\t\n").unwrap();
+        import(&mut test, &labels);
+        assert_eq!(test.app.status.as_deref(), Some("Imported 3 labels"));
+        assert_eq!(
+            markers(&test),
+            [
+                (100, Some("verse".into())),
+                (200, Some("chorus".into())),
+                (350, None),
+                (400, None),
+            ]
+        );
+
+        // nothing is added from an invalid file
+        std::fs::write(&labels, "0.1\t0.1\tok\n0.2\tsoon\tbad\n").unwrap();
+        import(&mut test, &labels);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot import labels: Line 2: invalid time \"soon\"")
+        );
+        std::fs::write(&labels, "0.1\n").unwrap();
+        import(&mut test, &labels);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot import labels: Line 1: expected 'start<TAB>end<TAB>label'")
+        );
+        std::fs::write(&labels, "0.3\t0.2\tbackwards\n").unwrap();
+        import(&mut test, &labels);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot import labels: Line 1: ends before it starts")
+        );
+        std::fs::write(&labels, "0.1\t9\tlong\n").unwrap();
+        import(&mut test, &labels);
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Cannot import labels: 9.000s is past the end of the audio")
+        );
+        assert_eq!(markers(&test).len(), 4);
+    }
+
+    #[test]
+    fn test_tui_nav_back() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        test.input(",");
+        assert_eq!(test.app.status.as_deref(), Some("No previous jump"));
+
+        test.input("llllllllll");
+        assert_eq!(test.app.cursor, ms(100));
+        test.app.apply_action(Action::SeekPercent(50)).unwrap();
+        assert_eq!(test.app.cursor, ms(250));
+
+        // stepping doesn't count as a jump, and the reversal is relative to the cursor
+        test.input("l,");
+        assert_eq!(test.app.cursor, ms(110));
+        // it repeats, unlike JumpBack
+        test.input(",");
+        assert_eq!(test.app.cursor, Duration::ZERO);
+        test.input("`");
+        assert_eq!(test.app.cursor, ms(110));
+
+        // a backwards jump is reversed forwards
+        test.app.apply_action(Action::CursorStart).unwrap();
+        test.input(",");
+        assert_eq!(test.app.cursor, ms(110));
+    }
+
+    #[test]
+    fn test_tui_confirm_destructive() {
+        let mut test = Test::load("sine440fade.wav");
+        let before: Vec<_> = test.app.source.clone().collect();
+
+        test.input("N");
+        assert!(matches!(test.app.mode, Mode::Confirm(Confirm::Normalize)));
+        assert!(test.render().contains("normalize the whole file? (y/n)"));
+        test.input("n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.source.clone().collect::<Vec<_>>(), before);
+
+        // cancelling an effect goes back to adjusting it
+        test.input("v%ai\n");
+        assert!(matches!(
+            test.app.mode,
+            Mode::Confirm(Confirm::ApplyEffect { .. })
+        ));
+        test.input("\x1b");
+        assert!(matches!(test.app.mode, Mode::Effect { selected: true, .. }));
+        assert_eq!(test.app.source.clone().collect::<Vec<_>>(), before);
+        test.input("\ny");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.edits, 1);
+
+        // a partial selection doesn't ask
+        test.input("vlai\n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.edits, 2);
+
+        let config = Config {
+            confirm_destructive: false,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        test.input("N");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.edits, 1);
+    }
+
+    #[test]
+    fn test_tui_focus() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input(&"l".repeat(10));
+        test.input("v");
+        test.input(&"l".repeat(20));
+        assert_snapshot!(test.render_styles());
+    }
+
+    #[test]
+    fn test_tui_extract_around() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            extract_radius: 100,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        let before = Audio::from(&test.app.source);
+        test.input(&"l".repeat(25));
+        assert_eq!(test.app.cursor, ms(250));
+
+        test.app.apply_action(Action::ExtractAround).unwrap();
+        assert!(test
+            .render()
+            .contains("discard everything away from the cursor? (y/n)"));
+        test.input("n");
+        assert_eq!(Audio::from(&test.app.source), before);
+        test.app.apply_action(Action::ExtractAround).unwrap();
+        test.input("y");
+        let after = Audio::from(&test.app.source);
+        assert_eq!(after.duration(), ms(200));
+        assert_eq!(after.samples, before.slice(ms(150), ms(350)));
+        assert_eq!(test.app.cursor, ms(100));
+        assert_eq!(test.app.status.as_deref(), Some("Extracted 0.20s"));
+
+        // clamped to the start of the file
+        test.input(&"h".repeat(5));
+        test.app.apply_action(Action::ExtractAround).unwrap();
+        test.input("y");
+        assert_eq!(
+            Audio::from(&test.app.source).samples,
+            before.slice(ms(150), ms(300))
+        );
+        assert_eq!(test.app.cursor, ms(50));
+
+        test.input("o");
+        assert_eq!(Audio::from(&test.app.source), after);
+    }
+
+    #[test]
+    fn test_tui_loop_all() {
+        let stop = |app: &mut App| {
+            app.sink.stop();
+            app.sink.sleep_until_end();
+            app.finish_playback();
+        };
+        let mut test = Test::load("sine440fade.wav");
+        test.input(&"l".repeat(10));
+        test.input(" ");
+        assert!(test.app.playing);
+
+        // playback stops at the end
+        stop(&mut test.app);
+        assert!(!test.app.playing);
+        assert!(test.app.sink.empty());
+
+        let key = KeyEvent::new(KeyCode::Char('l'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("Loop all on"));
+        test.input(" ");
+        stop(&mut test.app);
+        assert!(test.app.playing);
+        assert_eq!(test.app.sink.len(), 1);
+        assert_eq!(
+            test.app.looping,
+            Some((Duration::ZERO, test.app.duration()))
+        );
+
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("Loop all off"));
+        stop(&mut test.app);
+        assert!(!test.app.playing);
+    }
+
+    #[test]
+    fn test_tui_copy_timecode() {
+        assert_eq!(timecode(Duration::ZERO), "00:00.000");
+        assert_eq!(timecode(Duration::from_millis(61_005)), "01:01.005");
+        assert_eq!(timecode(Duration::from_secs(3723)), "62:03.000");
+
+        #[derive(Clone, Default)]
+        struct Clipboard(std::rc::Rc<RefCell<Vec<u8>>>);
+        impl std::io::Write for Clipboard {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut test = Test::load("sine440fade.wav");
+        let clipboard = Clipboard::default();
+        test.app.text_clipboard = Box::new(clipboard.clone());
+        test.input(&"l".repeat(25));
+        let key = KeyEvent::new(KeyCode::Char('c'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("Copied 00:00.250"));
+        assert_eq!(
+            String::from_utf8(clipboard.0.take()).unwrap(),
+            osc52::sequence("00:00.250")
+        );
+    }
+
+    #[test]
+    fn test_tui_play_at_end() {
+        let ms = Duration::from_millis;
+        let play_at_end = |play_at_end, length| {
+            let config = Config {
+                play_at_end,
+                play_at_end_length: length,
+                ..Default::default()
+            };
+            let mut test = Test::load_with_config("sine440fade.wav", config);
+            test.app.apply_action(Action::CursorEnd).unwrap();
+            test.input(" ");
+            test
+        };
+
+        let test = play_at_end(PlayAtEnd::Nothing, 3000);
+        assert!(!test.app.playing);
+        assert_eq!(test.app.cursor, ms(500));
+        assert_eq!(test.app.status.as_deref(), Some("Nothing to play"));
+
+        let test = play_at_end(PlayAtEnd::Rewind, 3000);
+        assert!(test.app.playing);
+        assert_eq!(test.app.cursor, Duration::ZERO);
+
+        let test = play_at_end(PlayAtEnd::Last, 200);
+        assert!(test.app.playing);
+        assert_eq!(test.app.cursor, ms(300));
+
+        // longer than the file
+        let test = play_at_end(PlayAtEnd::Last, 3000);
+        assert!(test.app.playing);
+        assert_eq!(test.app.cursor, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tui_paste_as_new() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.wav");
+        std::fs::copy("testdata/sine440fade.wav", &path).unwrap();
+        let mut test = Test::open(path, Config::default());
+        let alt_p = KeyEvent::new(KeyCode::Char('p'), crossterm::event::KeyModifiers::ALT);
+
+        test.app.handle_event(Event::Key(alt_p)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("Clipboard is empty"));
+
+        test.input("lllllvllllllllllly");
+        let clip = test.app.clipboard.clone().unwrap();
+        test.app.handle_event(Event::Key(alt_p)).unwrap();
+        let new = dir.path().join("fade_clip1.wav");
+        assert_eq!(
+            test.app.status,
+            Some(format!("Pasted into {}", new.display()))
+        );
+        let pasted = Audio::from(&load(&new).unwrap());
+        assert_eq!(pasted.duration(), Duration::from_millis(110));
+        assert_eq!(pasted.duration(), clip.duration());
+
+        // doesn't overwrite earlier clips
+        test.app.handle_event(Event::Key(alt_p)).unwrap();
+        assert!(dir.path().join("fade_clip2.wav").exists());
+        assert!(!test.app.dirty);
+    }
+
+    #[test]
+    fn test_tui_zoom_limits() {
+        let check = |app: &App| {
+            assert!(app.window_start < app.window_end);
+            assert!(app.window_end - app.window_start >= MIN_WINDOW);
+            assert!(app.window_end <= app.duration());
+        };
+        let mut test = Test::load("sine440fade.wav");
+        test.input(&"l".repeat(49));
+        for _ in 0..100 {
+            test.app.apply_action(Action::ZoomIn).unwrap();
+            test.app.apply_action(Action::ZoomInFast).unwrap();
+            check(&test.app);
+        }
+        test.render();
+        assert_eq!(test.app.window_end - test.app.window_start, MIN_WINDOW);
+
+        // zooming out near the end moves the start back rather than running past the end
+        test.app.apply_action(Action::CursorEnd).unwrap();
+        for _ in 0..100 {
+            test.app.apply_action(Action::ZoomOut).unwrap();
+            test.app.apply_action(Action::ZoomOutFast).unwrap();
+            check(&test.app);
+        }
+        test.render();
+        assert_eq!(test.app.window_start, Duration::ZERO);
+        assert_eq!(test.app.window_end, test.app.duration());
+    }
+
+    #[test]
+    fn test_tui_marker_category() {
+        let mut test = Test::load("sine440fade.wav");
+        let key = KeyEvent::new(KeyCode::Char('m'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("No markers"));
 
-        let sample_rate = self.source.sample_rate() as f64;
-        let start_secs = self.window_start.as_secs_f64();
-        let end_secs = self.window_end.as_secs_f64();
+        test.input(&"l".repeat(10));
+        test.input("m\n");
+        test.input(&"l".repeat(20));
+        test.input("m\n");
+        assert!(test.render_styles().contains('y'));
 
-        let wave_data: Vec<_> = self
-            .source
-            .clone()
-            .skip_duration(self.window_start)
-            .take_duration(self.window_end - self.window_start)
-            .enumerate()
-            .map(|(i, v)| (((i as f64) / sample_rate) + start_secs, v as f64))
+        // the nearest marker changes
+        test.input(&"h".repeat(8));
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("Marker at 0.300s is good"));
+        let categories = |app: &App| app.markers.iter().map(|m| m.category).collect::<Vec<_>>();
+        assert_eq!(categories(&test.app), [Category::Plain, Category::Good]);
+        let styles = test.render_styles();
+        assert!(styles.contains('y') && styles.contains('G'), "{styles}");
+
+        test.input(&"h".repeat(10));
+        test.app.handle_event(Event::Key(key)).unwrap();
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(categories(&test.app), [Category::Bad, Category::Good]);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        let styles = test.render_styles();
+        assert!(!styles.contains('y') && styles.contains('M'), "{styles}");
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(categories(&test.app), [Category::Plain, Category::Good]);
+    }
+
+    #[test]
+    fn test_tui_match_loudness() {
+        let mut test = Test::load("sine440fade.wav");
+        let key = KeyEvent::new(KeyCode::Char('n'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Load a reference to match")
+        );
+
+        let quiet: Vec<f32> = (0..100)
+            .map(|i| if i % 2 == 0 { 0.1 } else { -0.2 })
+            .collect();
+        let loud: Vec<f32> = (0..50)
+            .map(|i| if i % 2 == 0 { 0.4 } else { -0.8 })
             .collect();
+        test.app.source = SamplesBuffer::new(1, 100, quiet);
+        test.app.reference = Some(SamplesBuffer::new(1, 100, loud.clone()));
+        let rms = |app: &App, start, end| Audio::from(&app.source).rms(start, end).unwrap();
 
-        let selected_data: Vec<_> = match &self.mode {
-            Mode::Select(sel) => {
-                let (start, end) = sel.normalize();
-                let start = start.max(self.window_start);
-                let end = end.min(self.window_end);
-                self.source
-                    .clone()
-                    .skip_duration(start)
-                    .take_duration(end - start)
-                    .enumerate()
-                    .map(|(i, v)| (((i as f64) / sample_rate) + start.as_secs_f64(), v as f64))
-                    .collect()
-            }
-            Mode::Effect { selection, effect } => {
-                let (start, end) = selection.normalize();
-                let start = start.max(self.window_start);
-                let end = end.min(self.window_end);
-                let source = self
-                    .source
-                    .clone()
-                    .skip_duration(start)
-                    .take_duration(end - start);
-                let source = effect.apply(source);
-                source
-                    .enumerate()
-                    .map(|(i, v)| (((i as f64) / sample_rate) + start.as_secs_f64(), v as f64))
-                    .collect()
-            }
-            Mode::Normal => vec![],
+        // only the part overlapping the reference is measured, but the whole file is changed
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(matches!(
+            test.app.mode,
+            Mode::Confirm(Confirm::MatchLoudness)
+        ));
+        test.input("y");
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Matched the reference by +12.04dB")
+        );
+        let target = Audio::new(1, 100, loud).rms(Duration::ZERO, Duration::from_millis(500));
+        let matched = rms(&test.app, Duration::ZERO, Duration::from_millis(500));
+        assert!((matched - target.unwrap()).abs() < 1e-6, "{matched}");
+        assert_eq!(
+            rms(
+                &test.app,
+                Duration::from_millis(500),
+                Duration::from_secs(1)
+            ),
+            matched
+        );
+
+        // a selection past the end of the reference
+        test.input(&"l".repeat(60));
+        test.input("vl");
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("The reference doesn't overlap the selection")
+        );
+    }
+
+    #[test]
+    fn test_tui_open_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("takes")).unwrap();
+        std::fs::copy("testdata/sine440fade.wav", root.join("first.wav")).unwrap();
+        std::fs::copy("testdata/sine440fade.wav", root.join("takes/second.wav")).unwrap();
+        std::fs::copy("testdata/sine440fade.wav", root.join("takes/third.wav")).unwrap();
+        let mut test = Test::open(root.join("first.wav"), Config::default());
+        let alt_o = KeyEvent::new(KeyCode::Char('o'), crossterm::event::KeyModifiers::ALT);
+        let key = |test: &mut Test, code: KeyCode| {
+            test.app.handle_event(Event::Key(code.into())).unwrap()
         };
 
-        let cursor_data = [
-            (self.cursor.as_secs_f64(), -1.0),
-            (self.cursor.as_secs_f64(), 1.0),
-        ];
-        let mut datasets = vec![
-            // wave
-            Dataset::default()
-                .name(self.path.file_name().and_then(|f| f.to_str()).unwrap_or(""))
-                .marker(symbols::Marker::Braille)
-                .graph_type(GraphType::Line)
-                .style(Style::default().cyan())
-                .data(wave_data.as_slice()),
-            // selected
-            Dataset::default()
-                .marker(symbols::Marker::Braille)
-                .graph_type(GraphType::Line)
-                .style(Style::default().green())
-                .data(selected_data.as_slice()),
-            // cursor
-            Dataset::default()
-                .marker(symbols::Marker::Braille)
-                .graph_type(GraphType::Line)
-                .style(Style::default().white())
-                .data(&cursor_data),
-        ];
+        test.app.handle_event(Event::Key(alt_o)).unwrap();
+        let Mode::Pick(picker) = &test.app.mode else {
+            panic!("expected picker");
+        };
+        assert_eq!(picker.visible(), ["../", "takes/", "first.wav"]);
+        assert!(test.render().contains("open: "));
 
-        let selection_data = match self.mode {
-            Mode::Select(Selection { start, end })
-            | Mode::Effect {
-                selection: Selection { start, end },
-                ..
-            } => (
-                [(start.as_secs_f64(), -1.0), (start.as_secs_f64(), 1.0)],
-                [(end.as_secs_f64(), -1.0), (end.as_secs_f64(), 1.0)],
-            ),
-            Mode::Normal => ([(0.0, 0.0); 2], [(0.0, 0.0); 2]),
+        // filter down to the directory and go into it
+        test.input("tak");
+        test.input("\n");
+        let Mode::Pick(picker) = &test.app.mode else {
+            panic!("expected picker");
         };
+        assert_eq!(picker.dir, root.join("takes"));
+        assert_eq!(picker.visible(), ["../", "second.wav", "third.wav"]);
 
-        match self.mode {
-            Mode::Select(_) | Mode::Effect { .. } => {
-                datasets.push(
-                    Dataset::default()
-                        .marker(symbols::Marker::Braille)
-                        .graph_type(GraphType::Line)
-                        .style(Style::default().green())
-                        .data(&selection_data.0),
-                );
-                datasets.push(
-                    Dataset::default()
-                        .marker(symbols::Marker::Braille)
-                        .graph_type(GraphType::Line)
-                        .style(Style::default().green())
-                        .data(&selection_data.1),
-                )
-            }
-            Mode::Normal => {}
-        }
+        key(&mut test, KeyCode::Down);
+        key(&mut test, KeyCode::Down);
+        key(&mut test, KeyCode::Up);
+        test.input("\n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.path, root.join("takes/second.wav"));
+        assert_eq!(
+            test.app.status,
+            Some(format!(
+                "Opened {}",
+                root.join("takes/second.wav").display()
+            ))
+        );
 
-        let playhead_data = [
-            (self.playhead.as_secs_f64(), -1.0),
-            (self.playhead.as_secs_f64(), 1.0),
-        ];
-        if self.playing {
-            datasets.push(
-                Dataset::default()
-                    .marker(symbols::Marker::Braille)
-                    .graph_type(GraphType::Line)
-                    .style(Style::default().red())
-                    .data(&playhead_data),
-            )
-        }
+        // unsaved changes aren't thrown away without asking
+        test.input("vlx");
+        test.app.handle_event(Event::Key(alt_o)).unwrap();
+        test.input("third\n");
+        assert!(matches!(test.app.mode, Mode::Confirm(Confirm::Open(_))));
+        assert!(test.render().contains("discard changes and open"));
+        test.input("n");
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.path, root.join("takes/second.wav"));
+        assert!(test.app.dirty);
 
-        let x_axis = Axis::default()
-            .style(Style::default().white())
-            .bounds([start_secs, end_secs])
-            .labels([format!("{start_secs}s"), format!("{end_secs}s")]);
+        // nothing carries over from the previous file
+        test.app.effect_chain.push(Effect::Amplify { amount: 2.0 });
+        test.app.reference = Some(SamplesBuffer::new(1, 48000, vec![0.5; 480]));
+        test.app.loop_region = Some((Duration::ZERO, Duration::from_millis(100)));
+        test.app.loop_all = true;
+        test.app.handle_event(Event::Key(alt_o)).unwrap();
+        test.input("third\ny");
+        assert_eq!(test.app.path, root.join("takes/third.wav"));
+        assert!(!test.app.dirty);
+        assert!(test.app.effect_chain.is_empty());
+        assert!(test.app.reference.is_none());
+        assert!(test.app.loop_region.is_none());
+        assert!(!test.app.loop_all);
 
-        let y_axis = Axis::default()
-            .style(Style::default().white())
-            .bounds([-1.0, 1.0])
-            .labels(["0.0", "-1.0", "1.0"]);
+        // .. goes up a directory
+        test.app.handle_event(Event::Key(alt_o)).unwrap();
+        test.input("\n");
+        test.input("first\n");
+        assert_eq!(test.app.path, root.join("first.wav"));
+    }
 
-        let chart = Chart::new(datasets).x_axis(x_axis).y_axis(y_axis);
+    #[test]
+    fn test_tui_effect_compare() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input(&"l".repeat(10));
+        test.input("v");
+        test.input(&"l".repeat(20));
+        test.input("auuuu");
+        let without = test.render_styles();
+        assert!(!without.contains('o'));
 
-        chart.render(area, buf);
+        let key = KeyEvent::new(KeyCode::Char('v'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_snapshot!(test.render_styles());
+
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.render_styles(), without);
     }
-}
 
-pub fn start(config: Config, path: std::path::PathBuf) -> Result<()> {
-    let mut terminal = ratatui::init();
-    terminal.clear()?;
+    #[test]
+    fn test_tui_poll_interval() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            poll_interval_ms: 20,
+            max_fps: 10.0,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        let now = Instant::now();
+        test.app.drawn(now);
+        assert_eq!(test.app.poll_timeout(now), None);
 
-    let app_result = App::new(config, path)?.run(terminal);
-    ratatui::restore();
-    app_result
-}
+        test.input(" ");
+        test.app.drawn(now);
+        assert_eq!(test.app.poll_timeout(now), Some(ms(20)));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use event::KeyCode;
-    use insta::assert_snapshot;
+        // a pending redraw that is due sooner
+        test.app.needs_redraw = true;
+        assert_eq!(test.app.poll_timeout(now + ms(90)), Some(ms(10)));
 
-    struct Test {
-        app: App,
+        test.input(" ");
+        test.app.drawn(now);
+        assert_eq!(test.app.poll_timeout(now), None);
     }
 
-    impl Test {
-        fn load(path: &str) -> Test {
-            let app = App::new(
-                Config::default(),
-                std::path::Path::new("testdata").join(path).to_path_buf(),
-            )
-            .unwrap();
-            Test { app }
-        }
+    #[test]
+    fn test_tui_bounce() {
+        let mut test = Test::load("sine440fade.wav");
+        let key = KeyEvent::new(KeyCode::Char('b'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Load a reference to bounce")
+        );
 
-        fn render(&self) -> String {
-            let mut buf = Buffer::empty(layout::Rect::new(0, 0, 160, 20));
-            self.app.render(buf.area, &mut buf);
-            buf_string(&buf)
-        }
+        // a stereo reference at a different rate is converted
+        let before = Audio::from(&test.app.source);
+        test.app.reference = Some(SamplesBuffer::new(2, 24000, vec![0.5; 2 * 2400]));
+        test.app.handle_event(Event::Key(key)).unwrap();
+        let after = Audio::from(&test.app.source);
+        assert_eq!(
+            after.duration(),
+            before.duration() + Duration::from_millis(100)
+        );
+        assert_eq!(after.channels, 1);
+        assert_eq!(after.sample_rate, 48000);
+        assert_eq!(&after.samples[..before.samples.len()], before.samples);
+        assert_eq!(test.app.status.as_deref(), Some("Bounced to 0.600s"));
 
-        fn input(&mut self, keys: &str) {
-            let chars: Vec<_> = keys.chars().collect();
-            input(&mut self.app, chars.as_slice());
-        }
+        test.input("o");
+        assert_eq!(Audio::from(&test.app.source), before);
     }
 
-    fn buf_string(buf: &Buffer) -> String {
-        buf.content
-            .chunks(buf.area.width as usize)
-            .map(|line| {
-                line.iter()
-                    .map(|cell| cell.symbol().to_string())
-                    .collect::<Vec<_>>()
-                    .join("")
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+    #[test]
+    fn test_tui_center_cursor() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            center_cursor: true,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        test.app.window_end = ms(100);
+
+        // the view stays put until the cursor can be centered
+        test.input(&"l".repeat(3));
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(0), ms(100))
+        );
+
+        test.input(&"l".repeat(22));
+        assert_eq!(test.app.cursor, ms(250));
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(200), ms(300))
+        );
+        test.input("h");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(190), ms(290))
+        );
+
+        // clamped to the end of the file
+        test.app.apply_action(Action::CursorEnd).unwrap();
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(400), ms(500))
+        );
     }
 
-    fn input(app: &mut App, keys: &[char]) {
-        for c in keys {
-            app.handle_key_event(KeyCode::Char(*c).into()).unwrap();
-        }
+    #[test]
+    fn test_tui_reset_view() {
+        let mut test = Test::load("sine440fade.wav");
+        test.input("zz");
+        test.input(&"l".repeat(30));
+        test.input("vlllxvlla");
+        assert!(matches!(test.app.mode, Mode::Effect { .. }));
+        let edited = Audio::from(&test.app.source);
+
+        let key = KeyEvent::new(KeyCode::Char('r'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.cursor, Duration::ZERO);
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (Duration::ZERO, test.app.duration())
+        );
+        // the edit is kept
+        assert_eq!(Audio::from(&test.app.source), edited);
+        assert!(test.app.dirty);
+        assert!(!test.render().contains("overview"));
     }
 
     #[test]
-    fn test_tui_render_empty() {
-        let test = Test::load("sine440fade.wav");
-        assert_snapshot!("load", test.render());
+    fn test_tui_playhead_trail() {
+        let ms = Duration::from_millis;
+        let config = Config {
+            playhead_trail: true,
+            ..Default::default()
+        };
+        let mut test = Test::load_with_config("sine440fade.wav", config);
+        test.app.playing = true;
+        test.app.playhead = ms(250);
+        test.app.trail = Some([ms(240), ms(230), ms(220)].into());
+        assert_snapshot!(test.render_styles());
+
+        // off by default
+        let mut test = Test::load("sine440fade.wav");
+        test.app.playing = true;
+        test.app.playhead = ms(250);
+        assert!(test.app.trail.is_none());
+        let styles = test.render_styles();
+        assert!(styles.contains('r') && !styles.contains('1'), "{styles}");
     }
 
     #[test]
-    fn test_tui_move_cursor() {
+    fn test_tui_log_time() {
         let mut test = Test::load("sine440fade.wav");
+        test.app.cursor = Duration::from_millis(250);
+        let key = KeyEvent::new(KeyCode::Char('t'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(test.app.log_time);
+        assert_snapshot!(test.render());
 
-        test.input("llll");
-        assert_snapshot!("cursor_right", test.render());
+        // the cursor and the window edges stay in place
+        let ms = |ms: u64| Duration::from_millis(ms).as_secs_f64();
+        assert_eq!(test.app.time_x(ms(250)), ms(250));
+        assert_eq!(test.app.time_x(ms(0)), ms(0));
+        assert!((test.app.time_x(ms(500)) - ms(500)).abs() < 1e-9);
+        // and the area around the cursor is magnified
+        assert!(test.app.time_x(ms(260)) > ms(300));
+        assert!(test.app.time_x(ms(240)) < ms(200));
 
-        test.input("hh");
-        assert_snapshot!("cursor_left", test.render());
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert_eq!(test.app.time_x(ms(260)), ms(260));
+    }
 
-        test.input("gl");
-        assert_snapshot!("cursor_end", test.render());
+    #[test]
+    fn test_tui_measure_interval() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        test.app.apply_action(Action::MeasureInterval).unwrap();
+        assert_eq!(
+            test.app.status.as_deref(),
+            Some("Add two markers to measure between")
+        );
 
-        test.input("gs");
-        assert_snapshot!("cursor_start", test.render());
+        for pos in [ms(100), ms(350), ms(400)] {
+            test.app.add_marker(Marker {
+                pos,
+                label: None,
+                category: Category::Plain,
+            });
+        }
+
+        // before the first marker, measures the first two
+        test.app.apply_action(Action::MeasureInterval).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("0.250s (240.0 bpm)"));
+
+        // between markers
+        test.app.cursor = ms(370);
+        test.app.apply_action(Action::MeasureInterval).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("0.050s (1200.0 bpm)"));
+
+        // after the last marker, measures the last two
+        test.app.cursor = ms(450);
+        test.app.apply_action(Action::MeasureInterval).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("0.050s (1200.0 bpm)"));
+
+        test.app.cursor = ms(200);
+        test.app.apply_action(Action::MeasureInterval).unwrap();
+        assert_eq!(test.app.status.as_deref(), Some("0.250s (240.0 bpm)"));
     }
 
     #[test]
-    fn test_tui_zoom() {
+    fn test_tui_duplicate_all() {
         let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+        let key = KeyEvent::new(KeyCode::Char('D'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
 
-        let zoom0 = test.render();
-        assert_snapshot!("zoom0", zoom0);
+        let after = Audio::from(&test.app.source);
+        assert_eq!(after.duration(), before.duration() * 2);
+        let (first, second) = after.samples.split_at(before.samples.len());
+        assert_eq!(first, before.samples);
+        assert_eq!(second, before.samples);
+        assert_eq!(test.app.status.as_deref(), Some("Duplicated to 1.000s"));
 
-        test.input("z");
-        let zoom1 = test.render();
-        assert_snapshot!("zoom1", zoom1);
+        test.input("o");
+        assert_eq!(Audio::from(&test.app.source), before);
+    }
 
-        test.input("z");
-        let zoom2 = test.render();
-        assert_snapshot!("zoom2", zoom2);
+    #[test]
+    fn test_tui_region() {
+        let ms = Duration::from_millis;
+        let test = Test::load("sine440fade.wav");
+        let audio = Audio::from(&test.app.source);
+        for (start, end) in [
+            (ms(100), ms(200)),
+            (Duration::from_nanos(70_020_000), ms(600)),
+        ] {
+            assert_eq!(test.app.region(start, end).samples, audio.slice(start, end));
+        }
+        assert!(test.app.region(ms(200), ms(100)).samples.is_empty());
+    }
 
-        test.input(&"z".repeat(8));
-        let zoom10 = test.render();
-        assert_snapshot!("zoom10", zoom10);
+    #[test]
+    fn test_tui_anti_click() {
+        let ms = Duration::from_millis;
+        let test = Test::load("sine440fade.wav");
+        let region = test.app.region(ms(100), test.app.duration());
+        let source = Audio::from(&test.app.playback_source(ms(100)));
+        assert_eq!(source.samples.len(), region.samples.len());
 
-        // scroll past the right bound to scroll the view
-        test.input(&"l".repeat(6));
-        assert_snapshot!("zoom10right", test.render());
+        // ramps up over the first 5ms, then plays the audio untouched
+        let fade = source.index(ms(5));
+        assert_eq!(source.samples[0], 0.0);
+        let gains: Vec<_> = source.samples[..fade]
+            .iter()
+            .zip(&region.samples)
+            .filter(|(_, r)| r.abs() > 1e-3)
+            .map(|(s, r)| s / r)
+            .collect();
+        assert!(gains.windows(2).all(|w| w[0] <= w[1] + 1e-3), "{gains:?}");
+        assert!(gains[0] < 0.2, "{gains:?}");
+        let middle = source.index(ms(10))..source.index(ms(300));
+        assert_eq!(source.samples[middle.clone()], region.samples[middle]);
 
-        // should scroll back to where we were
-        test.input(&"h".repeat(6));
-        assert_eq!(zoom10, test.render());
+        let config = Config {
+            anti_click: 0,
+            ..Default::default()
+        };
+        let test = Test::load_with_config("sine440fade.wav", config);
+        let source = Audio::from(&test.app.playback_source(ms(100)));
+        assert_eq!(source.samples, region.samples);
+    }
 
-        test.input(&"Z".repeat(8));
-        assert_eq!(zoom2, test.render());
+    #[test]
+    fn test_tui_busy() {
+        let mut test = Test::load("sine440fade.wav");
+        let before = Audio::from(&test.app.source);
+        test.input("N");
+        // send the confirmation without the test's stand-in for the run loop
+        test.app
+            .handle_event(Event::Key(KeyCode::Char('y').into()))
+            .unwrap();
+        assert!(test.app.busy.is_some());
+        assert!(test.render().contains("Normalizing…"));
+        assert_eq!(Audio::from(&test.app.source), before);
 
-        test.input("Z");
-        assert_eq!(zoom1, test.render());
+        test.app.run_busy();
+        assert!(test.app.busy.is_none());
+        assert_ne!(Audio::from(&test.app.source), before);
+        assert!(!test.render().contains("Normalizing…"));
+    }
 
-        test.input("Z");
-        assert_eq!(zoom0, test.render());
+    #[test]
+    fn test_tui_zoom_presets() {
+        let ms = Duration::from_millis;
+        let mut test = Test::load("sine440fade.wav");
+        test.app
+            .set_source(Audio::new(1, 48000, vec![0.0; 48000 * 20]).into());
+        test.app.cursor = ms(5000);
+
+        test.input("d2");
+        assert_eq!(test.app.window_end - test.app.window_start, ms(1000));
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(4500), ms(5500))
+        );
+
+        test.input("d1");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(4950), ms(5050))
+        );
+
+        // clamped to the start of the file
+        test.app.cursor = ms(2000);
+        test.input("d3");
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(0), ms(10000))
+        );
+
+        // and the end
+        test.app.cursor = ms(19800);
+        test.app.apply_action(Action::ZoomOneSecond).unwrap();
+        assert_eq!(
+            (test.app.window_start, test.app.window_end),
+            (ms(19000), ms(20000))
+        );
     }
 
     #[test]
-    fn test_tui_select() {
+    fn test_tui_output_rate() {
         let mut test = Test::load("sine440fade.wav");
-        test.input("llllvlll");
-        assert_snapshot!("select_forward", test.render());
-        test.input("hhhhhh");
-        assert_snapshot!("select_backward", test.render());
+        let audio = Audio::from(&test.app.source);
+        let output = |app: &App| {
+            let source = app.to_output(app.playback_source(Duration::ZERO));
+            let (channels, rate) = (source.channels(), source.sample_rate());
+            Audio::new(channels, rate, source.collect())
+        };
+
+        test.app.output_rate = Some(48000);
+        let source = output(&test.app);
+        assert_eq!(source.sample_rate, 48000);
+        assert_eq!(source.samples.len(), audio.samples.len());
+
+        // the output rate differs, so playback is resampled but the source is not
+        test.app.output_rate = Some(44100);
+        let source = output(&test.app);
+        assert_eq!(source.sample_rate, 44100);
+        let diff = source.duration().abs_diff(audio.duration());
+        assert!(diff < Duration::from_millis(1), "{diff:?}");
+        assert_eq!(Audio::from(&test.app.source), audio);
+        assert_eq!(
+            dominant_frequency(&source.samples, source.sample_rate),
+            440.0
+        );
+
+        test.app.output_rate = None;
+        assert_eq!(output(&test.app).sample_rate, 48000);
     }
 
     #[test]
-    fn test_tui_select_all() {
+    fn test_tui_apply_to_complement() {
+        let ms = Duration::from_millis;
         let mut test = Test::load("sine440fade.wav");
-        test.input("%");
-        assert_snapshot!("select_all", test.render());
+        let before = Audio::from(&test.app.source);
+        let key = KeyEvent::new(KeyCode::Enter, crossterm::event::KeyModifiers::ALT);
+
+        // without a selection there's nothing outside it
+        test.input("ai");
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(matches!(test.app.mode, Mode::Effect { .. }));
+        assert_eq!(Audio::from(&test.app.source), before);
+        test.input("\x1b");
+
+        // 2x outside of 100-200ms
+        test.input("llllllllllvllllllllllaiiiiiiiiii");
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(matches!(test.app.mode, Mode::Normal));
+        assert_eq!(test.app.undo.len(), 1);
+        let after = Audio::from(&test.app.source);
+        let (start, end) = (before.index(ms(100)), before.index(ms(200)));
+        assert_eq!(after.samples[start..end], before.samples[start..end]);
+        for (a, b) in after.samples[..start].iter().zip(&before.samples[..start]) {
+            assert!((a - b * 2.0).abs() < 1e-6);
+        }
+        for (a, b) in after.samples[end..].iter().zip(&before.samples[end..]) {
+            assert!((a - b * 2.0).abs() < 1e-6);
+        }
     }
 
     #[test]
-    fn test_tui_amplify() {
+    fn test_tui_picker_small() {
         let mut test = Test::load("sine440fade.wav");
-        test.input("llllvlllaiii");
-        assert_snapshot!("amplify", test.render());
+        test.input("L");
+        let key = KeyEvent::new(KeyCode::Char('o'), crossterm::event::KeyModifiers::ALT);
+        test.app.handle_event(Event::Key(key)).unwrap();
+        assert!(matches!(test.app.mode, Mode::Pick(_)));
+        for (width, height) in [(80, 14), (20, 3), (3, 1)] {
+            let mut buf = Buffer::empty(layout::Rect::new(0, 0, width, height));
+            test.app.render(buf.area, &mut buf);
+        }
     }
 
     #[test]
-    fn test_tui_cut() {
+    fn test_tui_minimap_small() {
         let mut test = Test::load("sine440fade.wav");
-        test.input("llllvllllllllllllx");
-        assert_snapshot!("cut", test.render());
+        test.input("zz");
+        for (width, height) in [(3, 20), (1, 20), (160, 3)] {
+            let mut buf = Buffer::empty(layout::Rect::new(0, 0, width, height));
+            test.app.render(buf.area, &mut buf);
+        }
+
+        // the overview is only recomputed when the audio changes
+        test.render();
+        let cached = |app: &App| app.overview_cache.borrow().clone();
+        let before = cached(&test.app).unwrap();
+        test.input("l");
+        test.render();
+        assert_eq!(cached(&test.app).unwrap(), before);
+        test.input("vlllx");
+        assert!(cached(&test.app).is_none());
+        test.render();
+        assert_ne!(cached(&test.app).unwrap(), before);
+        test.input("o");
+        assert!(cached(&test.app).is_none());
     }
 }